@@ -0,0 +1,244 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand, ValueEnum};
+use d3ne::{
+    std_workers, to_dot, to_mermaid, Engine, ExecutionOverlay, Graph, GraphFormat, NodeEvent,
+    ProcessReport, RunContext, WorkersBuilder,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Work with d3ne graphs from the shell, graphs can be json, yaml or ron
+#[derive(Parser)]
+#[command(name = "d3ne", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check the connections and that every node has a standard worker
+    Validate { graph: PathBuf },
+    /// Run the graph with the standard workers and print the outputs of every node as json
+    Run {
+        graph: PathBuf,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Run the graph and print every node as it starts, finishes, fails or is skipped
+    Trace {
+        graph: PathBuf,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Print the graph as canonical json
+    Fmt {
+        graph: PathBuf,
+        /// Rewrite the file instead of printing it
+        #[arg(long)]
+        write: bool,
+    },
+    /// Print the graph as Graphviz DOT or Mermaid
+    Export {
+        graph: PathBuf,
+        #[arg(long, value_enum, default_value = "dot")]
+        format: ExportFormat,
+        /// Run the graph first and color nodes by what happened to them
+        #[arg(long)]
+        run: bool,
+        #[arg(long)]
+        start: Option<i64>,
+    },
+    /// Count the nodes per worker
+    Stats { graph: PathBuf },
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
+    /// Node to start from, defaults to the first node without connected inputs
+    #[arg(long)]
+    start: Option<i64>,
+    /// Run parameters as json
+    #[arg(long)]
+    params: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Dot,
+    Mermaid,
+}
+
+fn engine(graph: &Graph) -> Engine<'_> {
+    let mut workers = WorkersBuilder::new();
+    std_workers::register(&mut workers);
+    Engine::new(&graph.id, workers.build())
+}
+
+fn start_node(graph: &Graph, start: Option<i64>) -> Result<i64> {
+    if let Some(start) = start {
+        if !graph.nodes.contains_key(&start) {
+            anyhow::bail!("Start node {} does not exist", start);
+        }
+        return Ok(start);
+    }
+    graph
+        .entry_node()
+        .ok_or_else(|| anyhow::anyhow!("Graph has no node without inputs, pass --start"))
+}
+
+fn run(engine: &Engine, graph: &Graph, args: &RunArgs) -> Result<ProcessReport> {
+    engine.validate(graph)?;
+    let params = match &args.params {
+        Some(params) => serde_json::from_str(params)?,
+        None => Value::Null,
+    };
+    let start = start_node(graph, args.start)?;
+    Ok(engine.process_all_with(&graph.nodes, start, RunContext::new().params(params)))
+}
+
+fn outputs(report: &ProcessReport) -> Result<Value> {
+    let outputs = report
+        .outputs
+        .iter()
+        .map(|(id, output)| Ok((id.to_string(), output.to_json()?)))
+        .collect::<Result<BTreeMap<_, _>>>()?;
+    Ok(json!(outputs))
+}
+
+/// Prints the errors of the report and turns them into the exit code
+fn finish(report: &ProcessReport) -> ExitCode {
+    for (id, error) in &report.errors {
+        eprintln!("node {}: {}", id, error);
+    }
+    if report.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn execute(cli: Cli) -> Result<ExitCode> {
+    match cli.command {
+        Command::Validate { graph: path } => {
+            let graph = Graph::load(&path)?;
+            engine(&graph).validate(&graph)?;
+            println!("{}: {} nodes ok", path.display(), graph.nodes.len());
+        }
+        Command::Run { graph, run: args } => {
+            let graph = Graph::load(graph)?;
+            let report = run(&engine(&graph), &graph, &args)?;
+            println!("{}", serde_json::to_string_pretty(&outputs(&report)?)?);
+            return Ok(finish(&report));
+        }
+        Command::Trace { graph, run: args } => {
+            let graph = Graph::load(graph)?;
+            let mut engine = engine(&graph);
+            engine.set_observer(|event| match event {
+                NodeEvent::Started {
+                    node_id, worker, ..
+                } => println!("start  {} {}", node_id, worker),
+                NodeEvent::Finished {
+                    node_id,
+                    worker,
+                    outputs,
+                    elapsed,
+                    ..
+                } => println!(
+                    "done   {} {} {:?} {}",
+                    node_id,
+                    worker,
+                    elapsed,
+                    outputs
+                        .to_json()
+                        .map_or_else(|e| e.to_string(), |v| v.to_string())
+                ),
+                NodeEvent::Failed {
+                    node_id,
+                    worker,
+                    error,
+                    elapsed,
+                    ..
+                } => println!("failed {} {} {:?} {}", node_id, worker, elapsed, error),
+                NodeEvent::Skipped { node_id, .. } => println!("skip   {}", node_id),
+            });
+            let report = run(&engine, &graph, &args)?;
+            for id in &report.disabled {
+                println!("closed {}", id);
+            }
+            return Ok(finish(&report));
+        }
+        Command::Fmt { graph: path, write } => {
+            let graph = Graph::load(&path)?;
+            if write {
+                let format = GraphFormat::from_path(&path).unwrap_or(GraphFormat::Json);
+                std::fs::write(&path, graph.to_format(format)?)?;
+            } else {
+                println!("{}", graph.to_format(GraphFormat::Json)?);
+            }
+        }
+        Command::Export {
+            graph,
+            format,
+            run: with_run,
+            start,
+        } => {
+            let graph = Graph::load(graph)?;
+            let overlay = if with_run {
+                let args = RunArgs {
+                    start,
+                    params: None,
+                };
+                Some(ExecutionOverlay::from(&run(
+                    &engine(&graph),
+                    &graph,
+                    &args,
+                )?))
+            } else {
+                None
+            };
+            let output = match format {
+                ExportFormat::Dot => to_dot(&graph.nodes, overlay.as_ref()),
+                ExportFormat::Mermaid => to_mermaid(&graph.nodes, overlay.as_ref()),
+            };
+            print!("{}", output);
+        }
+        Command::Stats { graph } => {
+            let graph = Graph::load(graph)?;
+            let mut workers = BTreeMap::<&str, usize>::new();
+            for node in graph.nodes.values() {
+                *workers.entry(&node.name).or_default() += 1;
+            }
+            let connections = graph
+                .nodes
+                .values()
+                .flat_map(|node| node.outputs.iter().flat_map(|outputs| outputs.values()))
+                .map(|output| output.connections.len())
+                .sum::<usize>();
+            println!("graph       {}", graph.id);
+            println!("nodes       {}", graph.nodes.len());
+            println!("connections {}", connections);
+            println!(
+                "groups      {}",
+                graph.groups.as_ref().map_or(0, |g| g.len())
+            );
+            println!("comments    {}", graph.comments().len());
+            for (worker, count) in workers {
+                println!("  {:<10} {}", worker, count);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    match execute(Cli::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::graph::Graph;
+use crate::node::{Node, OutputData};
+use crate::target::{Input, InputConnection, Inputs, Output, OutputConnection, Outputs};
+use anyhow::Result;
+use serde_json::{Map, Number, Value};
+use std::collections::{BTreeMap, HashMap};
+
+const MAGIC: &[u8; 4] = b"D3NE";
+pub const BINARY_FORMAT_VERSION: u16 = 1;
+
+#[derive(Clone, Copy)]
+#[repr(u8)]
+enum Kind {
+    Graph = 1,
+    Outputs = 2,
+}
+
+/// Bincode can't decode `serde_json::Value` since it isn't self describing
+#[derive(Serialize, Deserialize)]
+enum BinValue {
+    Null,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Array(Vec<BinValue>),
+    Object(Vec<(String, BinValue)>),
+}
+
+impl From<&Value> for BinValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => BinValue::Null,
+            Value::Bool(b) => BinValue::Bool(*b),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => BinValue::I64(i),
+                (None, Some(u)) => BinValue::U64(u),
+                _ => BinValue::F64(n.as_f64().unwrap_or_default()),
+            },
+            Value::String(s) => BinValue::String(s.clone()),
+            Value::Array(a) => BinValue::Array(a.iter().map(BinValue::from).collect()),
+            Value::Object(o) => BinValue::Object(
+                o.iter()
+                    .map(|(k, v)| (k.clone(), BinValue::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<BinValue> for Value {
+    fn from(value: BinValue) -> Self {
+        match value {
+            BinValue::Null => Value::Null,
+            BinValue::Bool(b) => Value::Bool(b),
+            BinValue::I64(i) => Value::Number(i.into()),
+            BinValue::U64(u) => Value::Number(u.into()),
+            BinValue::F64(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+            BinValue::String(s) => Value::String(s),
+            BinValue::Array(a) => Value::Array(a.into_iter().map(Value::from).collect()),
+            BinValue::Object(o) => Value::Object(
+                o.into_iter()
+                    .map(|(k, v)| (k, Value::from(v)))
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+type BinSockets = Vec<(String, Vec<(i64, String, BinValue)>)>;
+
+#[derive(Serialize, Deserialize)]
+struct BinNode {
+    id: i64,
+    name: String,
+    data: Option<BinValue>,
+    group: Option<i64>,
+    position: Option<Vec<f64>>,
+    inputs: Option<BinSockets>,
+    outputs: Option<BinSockets>,
+    extra: BinValue,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinGraph {
+    id: String,
+    nodes: Vec<BinNode>,
+    comments: Option<BinValue>,
+    groups: Option<BinValue>,
+    extra: BinValue,
+}
+
+impl From<&Node> for BinNode {
+    fn from(node: &Node) -> Self {
+        BinNode {
+            id: node.id,
+            name: node.name.clone(),
+            data: node.data.as_ref().map(BinValue::from),
+            group: node.group,
+            position: node.position.clone(),
+            inputs: node.inputs.as_ref().map(|inputs| {
+                inputs
+                    .iter()
+                    .map(|(name, input)| {
+                        let connections = input
+                            .connections
+                            .iter()
+                            .map(|c| (c.node, c.output.clone(), BinValue::from(&c.data)))
+                            .collect();
+                        (name.clone(), connections)
+                    })
+                    .collect()
+            }),
+            outputs: node.outputs.as_ref().map(|outputs| {
+                outputs
+                    .iter()
+                    .map(|(name, output)| {
+                        let connections = output
+                            .connections
+                            .iter()
+                            .map(|c| (c.node, c.input.clone(), BinValue::from(&c.data)))
+                            .collect();
+                        (name.clone(), connections)
+                    })
+                    .collect()
+            }),
+            extra: BinValue::from(&Value::Object(node.extra.clone())),
+        }
+    }
+}
+
+impl From<BinNode> for Node {
+    fn from(node: BinNode) -> Self {
+        Node {
+            id: node.id,
+            name: node.name,
+            data: node.data.map(Value::from),
+            group: node.group,
+            position: node.position,
+            inputs: node.inputs.map(|inputs| {
+                let mut result = Inputs::default();
+                for (name, connections) in inputs {
+                    let connections = connections
+                        .into_iter()
+                        .map(|(node, output, data)| InputConnection {
+                            node,
+                            output,
+                            data: data.into(),
+                        })
+                        .collect();
+                    result.inner_mut().insert(name, Input { connections });
+                }
+                result
+            }),
+            outputs: node.outputs.map(|outputs| {
+                let mut result = Outputs::default();
+                for (name, connections) in outputs {
+                    let connections = connections
+                        .into_iter()
+                        .map(|(node, input, data)| OutputConnection {
+                            node,
+                            input,
+                            data: data.into(),
+                        })
+                        .collect();
+                    result.inner_mut().insert(name, Output { connections });
+                }
+                result
+            }),
+            extra: match Value::from(node.extra) {
+                Value::Object(extra) => extra,
+                _ => Map::new(),
+            },
+        }
+    }
+}
+
+fn header(kind: Kind) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&BINARY_FORMAT_VERSION.to_le_bytes());
+    bytes.push(kind as u8);
+    bytes
+}
+
+fn body(bytes: &[u8], kind: Kind) -> Result<&[u8]> {
+    if bytes.len() < 7 || &bytes[..4] != MAGIC {
+        bail!("Not a d3ne binary document");
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != BINARY_FORMAT_VERSION {
+        bail!(
+            "Binary format version {} is not supported, expected {}",
+            version,
+            BINARY_FORMAT_VERSION
+        );
+    }
+    if bytes[6] != kind as u8 {
+        bail!(
+            "Binary document holds kind {}, expected {}",
+            bytes[6],
+            kind as u8
+        );
+    }
+    Ok(&bytes[7..])
+}
+
+impl Graph {
+    /// Compact encoding that loads faster than the editor json
+    pub fn to_binary(&self) -> Result<Vec<u8>> {
+        let graph = BinGraph {
+            id: self.id.clone(),
+            nodes: self.nodes.values().map(BinNode::from).collect(),
+            comments: self
+                .comments
+                .as_ref()
+                .map(|c| Ok::<_, serde_json::Error>(BinValue::from(&serde_json::to_value(c)?)))
+                .transpose()?,
+            groups: self
+                .groups
+                .as_ref()
+                .map(|g| Ok::<_, serde_json::Error>(BinValue::from(&serde_json::to_value(g)?)))
+                .transpose()?,
+            extra: BinValue::from(&Value::Object(self.extra.clone())),
+        };
+        let mut bytes = header(Kind::Graph);
+        bincode::serialize_into(&mut bytes, &graph)?;
+        Ok(bytes)
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Graph> {
+        let graph: BinGraph = bincode::deserialize(body(bytes, Kind::Graph)?)?;
+        let extra = match Value::from(graph.extra) {
+            Value::Object(extra) => extra,
+            _ => Map::new(),
+        };
+        Ok(Graph {
+            id: graph.id,
+            nodes: graph
+                .nodes
+                .into_iter()
+                .map(|node| (node.id, Node::from(node)))
+                .collect(),
+            comments: graph
+                .comments
+                .map(|c| serde_json::from_value(c.into()))
+                .transpose()?,
+            groups: graph
+                .groups
+                .map(|g| serde_json::from_value(g.into()))
+                .transpose()?,
+            extra,
+        })
+    }
+}
+
+/// Encodes the outputs of a run, every output has to convert to json
+pub fn encode_outputs(outputs: &HashMap<i64, OutputData>) -> Result<Vec<u8>> {
+    let outputs = outputs
+        .iter()
+        .map(|(id, output)| Ok((*id, BinValue::from(&output.to_json()?))))
+        .collect::<Result<Vec<_>>>()?;
+    let mut bytes = header(Kind::Outputs);
+    bincode::serialize_into(&mut bytes, &outputs)?;
+    Ok(bytes)
+}
+
+pub fn decode_outputs(bytes: &[u8]) -> Result<BTreeMap<i64, Value>> {
+    let outputs: Vec<(i64, BinValue)> = bincode::deserialize(body(bytes, Kind::Outputs)?)?;
+    Ok(outputs
+        .into_iter()
+        .map(|(id, output)| (id, output.into()))
+        .collect())
+}
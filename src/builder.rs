@@ -0,0 +1,109 @@
+use crate::graph::{Graph, GraphError};
+use crate::node::Node;
+use crate::target::{Inputs, Outputs};
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Builds graphs in code, every `connect` fills in both the output and the input side
+pub struct GraphBuilder {
+    graph: Graph,
+    next_id: i64,
+    connections: Vec<((i64, String), (i64, String))>,
+}
+
+impl GraphBuilder {
+    pub fn new(id: &str) -> GraphBuilder {
+        GraphBuilder {
+            graph: Graph::new(id),
+            next_id: 1,
+            connections: vec![],
+        }
+    }
+
+    pub fn node(mut self, id: i64, name: &str, data: Value) -> Self {
+        self.insert_node(id, name, data);
+        self
+    }
+
+    /// Adds a node with the next free id and returns that id
+    pub fn add_node(&mut self, name: &str, data: Value) -> i64 {
+        let id = self.next_id;
+        self.insert_node(id, name, data);
+        id
+    }
+
+    /// Declares an input socket without connecting it, `connect` declares the sockets it uses
+    pub fn input(mut self, node: i64, name: &str) -> Result<Self, GraphError> {
+        self.graph.add_input(node, name)?;
+        Ok(self)
+    }
+
+    /// Declares an output socket without connecting it
+    pub fn output(mut self, node: i64, name: &str) -> Result<Self, GraphError> {
+        self.graph.add_output(node, name)?;
+        Ok(self)
+    }
+
+    pub fn position(mut self, node: i64, x: f64, y: f64) -> Self {
+        if let Some(node) = self.graph.nodes.get_mut(&node) {
+            node.position = Some(vec![x, y]);
+        }
+        self
+    }
+
+    pub fn connect(mut self, from: (i64, &str), to: (i64, &str)) -> Self {
+        self.add_connection(from, to);
+        self
+    }
+
+    pub fn add_connection(&mut self, from: (i64, &str), to: (i64, &str)) -> &mut Self {
+        self.connections
+            .push(((from.0, from.1.to_string()), (to.0, to.1.to_string())));
+        self
+    }
+
+    /// Same nodes `Engine::parse_json` produces for the equivalent editor json
+    pub fn build(self) -> Result<HashMap<i64, Node>> {
+        Ok(self.build_graph()?.nodes)
+    }
+
+    pub fn build_graph(mut self) -> Result<Graph> {
+        for ((from, output), (to, input)) in std::mem::take(&mut self.connections) {
+            self.graph
+                .add_output(from, &output)
+                .or_else(existing)
+                .and_then(|_| self.graph.add_input(to, &input).or_else(existing))
+                .and_then(|_| self.graph.connect((from, &output), (to, &input)))
+                .map_err(|e| {
+                    anyhow!("Connection {}.{} -> {}.{}: {}", from, output, to, input, e)
+                })?;
+        }
+        Ok(self.graph)
+    }
+
+    fn insert_node(&mut self, id: i64, name: &str, data: Value) {
+        self.next_id = self.next_id.max(id + 1);
+        self.graph.nodes.insert(
+            id,
+            Node {
+                id,
+                name: name.to_string(),
+                data: Some(data),
+                group: None,
+                position: None,
+                inputs: Some(Inputs::default()),
+                outputs: Some(Outputs::default()),
+                extra: Default::default(),
+            },
+        );
+    }
+}
+
+/// Sockets declared earlier are reused by `connect`
+fn existing(e: GraphError) -> Result<(), GraphError> {
+    match e {
+        GraphError::SocketExists(..) => Ok(()),
+        e => Err(e),
+    }
+}
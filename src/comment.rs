@@ -0,0 +1,123 @@
+use serde_json::{Map, Value};
+
+/// Comment from the editor comment plugin, `links` are the ids of the attached nodes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Comment {
+    Frame(FrameComment),
+    Inline(InlineComment),
+    /// Comment with a missing or unknown `type`, kept as is so the graph still round trips
+    #[serde(untagged)]
+    Other(Value),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FrameComment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub text: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::node::position"
+    )]
+    pub position: Option<Vec<f64>>,
+    #[serde(default)]
+    pub links: Vec<i64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::node::dimension"
+    )]
+    pub width: Option<f64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::node::dimension"
+    )]
+    pub height: Option<f64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InlineComment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub text: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::node::position"
+    )]
+    pub position: Option<Vec<f64>>,
+    #[serde(default)]
+    pub links: Vec<i64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Comment {
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Comment::Frame(c) => c.id.as_deref(),
+            Comment::Inline(c) => c.id.as_deref(),
+            Comment::Other(value) => value.get("id").and_then(Value::as_str),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            Comment::Frame(c) => &c.text,
+            Comment::Inline(c) => &c.text,
+            Comment::Other(value) => value.get("text").and_then(Value::as_str).unwrap_or(""),
+        }
+    }
+
+    pub fn position(&self) -> Option<&[f64]> {
+        match self {
+            Comment::Frame(c) => c.position.as_deref(),
+            Comment::Inline(c) => c.position.as_deref(),
+            Comment::Other(_) => None,
+        }
+    }
+
+    /// Links of the known comment types, see `is_linked_to` for every comment
+    pub fn links(&self) -> &[i64] {
+        match self {
+            Comment::Frame(c) => &c.links,
+            Comment::Inline(c) => &c.links,
+            Comment::Other(_) => &[],
+        }
+    }
+
+    pub fn is_linked_to(&self, node_id: i64) -> bool {
+        match self {
+            Comment::Other(value) => value
+                .get("links")
+                .and_then(Value::as_array)
+                .is_some_and(|links| links.iter().any(|link| link.as_i64() == Some(node_id))),
+            _ => self.links().contains(&node_id),
+        }
+    }
+
+    /// Detaches the comment from the node
+    pub fn unlink(&mut self, node_id: i64) {
+        match self {
+            Comment::Frame(c) => c.links.retain(|link| *link != node_id),
+            Comment::Inline(c) => c.links.retain(|link| *link != node_id),
+            Comment::Other(value) => {
+                if let Some(links) = value.get_mut("links").and_then(Value::as_array_mut) {
+                    links.retain(|link| link.as_i64() != Some(node_id));
+                }
+            }
+        }
+    }
+
+    /// Case insensitive search in the comment text
+    pub fn matches(&self, pattern: &str) -> bool {
+        self.text().to_lowercase().contains(&pattern.to_lowercase())
+    }
+}
@@ -0,0 +1,249 @@
+use crate::node::OutputData;
+use crate::state::StateStore;
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Typed map of shared services (database pools, config, clocks) handed to every worker
+#[derive(Default)]
+pub struct Extensions(HashMap<TypeId, Box<dyn Any>>);
+
+impl Extensions {
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.0
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast::<T>().ok().map(|old| *old))
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.0
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.0
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast::<T>().ok().map(|value| *value))
+    }
+}
+
+/// Cancels a run from another thread, the engine checks it before every node
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    pub fn new() -> Cancellation {
+        Cancellation::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug)]
+pub struct LogRecord<'a> {
+    pub run_id: u64,
+    pub node_id: i64,
+    pub worker: &'a str,
+    pub level: LogLevel,
+    pub message: fmt::Arguments<'a>,
+}
+
+pub type Logger = Rc<dyn Fn(&LogRecord)>;
+
+/// What happened to a node during a run, see `Engine::set_observer`
+#[derive(Debug)]
+pub enum NodeEvent<'a> {
+    Started {
+        run_id: u64,
+        node_id: i64,
+        worker: &'a str,
+    },
+    Finished {
+        run_id: u64,
+        node_id: i64,
+        worker: &'a str,
+        outputs: &'a OutputData,
+        elapsed: Duration,
+    },
+    Failed {
+        run_id: u64,
+        node_id: i64,
+        worker: &'a str,
+        error: &'a anyhow::Error,
+        elapsed: Duration,
+    },
+    /// An input of the node failed or was skipped itself
+    Skipped { run_id: u64, node_id: i64 },
+}
+
+impl NodeEvent<'_> {
+    pub fn node_id(&self) -> i64 {
+        match self {
+            NodeEvent::Started { node_id, .. }
+            | NodeEvent::Finished { node_id, .. }
+            | NodeEvent::Failed { node_id, .. }
+            | NodeEvent::Skipped { node_id, .. } => *node_id,
+        }
+    }
+}
+
+pub type Observer = Rc<dyn Fn(&NodeEvent)>;
+
+/// Per run state passed to `Engine::process_with`
+#[derive(Clone, Debug)]
+pub struct RunContext {
+    pub run_id: u64,
+    /// Separates the node state of different graphs run on one engine. Every entry point
+    /// defaults to the empty id, graphs sharing an engine should each set their own
+    pub graph_id: String,
+    pub params: Value,
+    pub cancellation: Cancellation,
+}
+
+impl RunContext {
+    pub fn new() -> RunContext {
+        RunContext {
+            run_id: NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed),
+            graph_id: String::new(),
+            params: Value::Null,
+            cancellation: Cancellation::default(),
+        }
+    }
+
+    pub fn graph_id(mut self, graph_id: &str) -> RunContext {
+        self.graph_id = graph_id.to_string();
+        self
+    }
+
+    pub fn params(mut self, params: Value) -> RunContext {
+        self.params = params;
+        self
+    }
+
+    pub fn cancellation(mut self, cancellation: Cancellation) -> RunContext {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+impl Default for RunContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a worker sees of the run it belongs to, scoped to the node being worked
+pub struct Context<'r> {
+    run: &'r RunContext,
+    extensions: &'r Extensions,
+    state: &'r dyn StateStore,
+    logger: Option<&'r Logger>,
+    node_id: i64,
+    worker: &'r str,
+}
+
+impl<'r> Context<'r> {
+    pub fn new(
+        run: &'r RunContext,
+        extensions: &'r Extensions,
+        state: &'r dyn StateStore,
+        logger: Option<&'r Logger>,
+        node_id: i64,
+        worker: &'r str,
+    ) -> Context<'r> {
+        Context {
+            run,
+            extensions,
+            state,
+            logger,
+            node_id,
+            worker,
+        }
+    }
+
+    pub fn run_id(&self) -> u64 {
+        self.run.run_id
+    }
+
+    pub fn node_id(&self) -> i64 {
+        self.node_id
+    }
+
+    pub fn graph_id(&self) -> &str {
+        &self.run.graph_id
+    }
+
+    pub fn params(&self) -> &Value {
+        &self.run.params
+    }
+
+    pub fn param(&self, name: &str) -> Option<&Value> {
+        self.run.params.get(name)
+    }
+
+    pub fn extension<T: 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+
+    /// State this node stored in a previous run
+    pub fn state<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        self.state
+            .get(&self.run.graph_id, self.node_id)?
+            .map(|value| Ok(serde_json::from_value::<T>(value)?))
+            .transpose()
+    }
+
+    pub fn set_state<T: Serialize>(&self, value: &T) -> Result<()> {
+        self.state.set(
+            &self.run.graph_id,
+            self.node_id,
+            serde_json::to_value(value)?,
+        )
+    }
+
+    pub fn reset_state(&self) -> Result<()> {
+        self.state.reset(&self.run.graph_id, self.node_id)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.run.cancellation.is_cancelled()
+    }
+
+    pub fn log(&self, level: LogLevel, message: fmt::Arguments) {
+        if let Some(logger) = self.logger {
+            logger(&LogRecord {
+                run_id: self.run.run_id,
+                node_id: self.node_id,
+                worker: self.worker,
+                level,
+                message,
+            });
+        }
+    }
+}
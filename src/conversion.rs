@@ -0,0 +1,115 @@
+use crate::node::IOData;
+use anyhow::Result;
+use serde_json::{Number, Value};
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type ConvertFn = Rc<dyn Fn(&dyn Any) -> Result<Box<dyn Any>>>;
+
+/// Registry of converters between socket value types, used by the `Node` getters
+/// when an upstream node outputs a different type than the one being read
+#[derive(Clone)]
+pub struct Conversions(HashMap<(TypeId, TypeId), ConvertFn>);
+
+impl Conversions {
+    /// Registry without any converters
+    pub fn empty() -> Conversions {
+        Conversions(HashMap::new())
+    }
+
+    pub fn register<A, B, F>(&mut self, convert: F) -> &mut Self
+    where
+        A: 'static,
+        B: 'static,
+        F: Fn(&A) -> Result<B> + 'static,
+    {
+        self.0.insert(
+            (TypeId::of::<A>(), TypeId::of::<B>()),
+            Rc::new(move |value: &dyn Any| {
+                let value = value
+                    .downcast_ref::<A>()
+                    .ok_or(anyhow!("Converter input is not `{}`", type_name::<A>()))?;
+                Ok(Box::new(convert(value)?) as Box<dyn Any>)
+            }),
+        );
+        self
+    }
+
+    pub fn can_convert<A: 'static, B: 'static>(&self) -> bool {
+        self.can_convert_id(TypeId::of::<A>(), TypeId::of::<B>())
+    }
+
+    pub fn can_convert_id(&self, from: TypeId, to: TypeId) -> bool {
+        from == to || self.0.contains_key(&(from, to))
+    }
+
+    /// Whether `data` can be read as `B`, json being reachable from every serializable value
+    pub fn can_convert_data<B: 'static>(&self, data: &IOData) -> bool {
+        let to = TypeId::of::<B>();
+        self.can_convert_id(data.as_any().type_id(), to)
+            || (to == TypeId::of::<Value>() && data.value().is_some())
+    }
+
+    pub fn convert<B>(&self, data: &IOData) -> Result<B>
+    where
+        B: Clone + 'static,
+    {
+        if let Some(value) = data.get::<B>() {
+            return Ok(value.clone());
+        }
+        let from = data.as_any().type_id();
+        let convert = self.0.get(&(from, TypeId::of::<B>())).ok_or(anyhow!(
+            "No conversion registered from {:?} to `{}`",
+            data,
+            type_name::<B>()
+        ))?;
+        convert(data.as_any())?
+            .downcast::<B>()
+            .map(|b| *b)
+            .map_err(|_| anyhow!("Converter did not return `{}`", type_name::<B>()))
+    }
+
+    /// Registered converters take precedence, typed values fall back to their serde representation
+    pub fn to_json(&self, data: &IOData) -> Result<Value> {
+        if self.can_convert_id(data.as_any().type_id(), TypeId::of::<Value>()) {
+            self.convert::<Value>(data)
+        } else {
+            data.to_json()
+        }
+    }
+}
+
+impl Default for Conversions {
+    fn default() -> Self {
+        let mut conversions = Conversions::empty();
+        conversions
+            .register(|v: &i32| Ok(*v as i64))
+            .register(|v: &u32| Ok(*v as i64))
+            .register(|v: &i32| Ok(*v as f64))
+            .register(|v: &i64| Ok(*v as f64))
+            .register(|v: &f32| Ok(*v as f64))
+            .register(|v: &bool| Ok(Value::Bool(*v)))
+            .register(|v: &i32| Ok(Value::Number(Number::from(*v))))
+            .register(|v: &i64| Ok(Value::Number(Number::from(*v))))
+            .register(|v: &u64| Ok(Value::Number(Number::from(*v))))
+            .register(|v: &f32| to_json_number(*v as f64))
+            .register(|v: &f64| to_json_number(*v))
+            .register(|v: &String| Ok(Value::String(v.clone())))
+            .register(|v: &Value| v.as_i64().ok_or(anyhow!("{} is not an i64", v)))
+            .register(|v: &Value| v.as_f64().ok_or(anyhow!("{} is not an f64", v)))
+            .register(|v: &Value| v.as_bool().ok_or(anyhow!("{} is not a bool", v)))
+            .register(|v: &Value| {
+                v.as_str()
+                    .map(str::to_owned)
+                    .ok_or(anyhow!("{} is not a string", v))
+            });
+        conversions
+    }
+}
+
+fn to_json_number(v: f64) -> Result<Value> {
+    Number::from_f64(v)
+        .map(Value::Number)
+        .ok_or(anyhow!("{} is not a valid json number", v))
+}
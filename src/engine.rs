@@ -1,30 +1,265 @@
+use crate::context::{Context, Extensions, LogRecord, Logger, NodeEvent, Observer, RunContext};
+use crate::conversion::Conversions;
+use crate::format::read_value;
+use crate::graph::Graph;
+use crate::migration::{AppliedMigration, Migration, Migrations};
+use crate::state::{MemoryStateStore, Snapshot, StateStore};
+use crate::version::Compatibility;
 use crate::workers::Workers;
 use crate::{node::*, WorkerError};
 use anyhow::Result;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::Path;
 use std::rc::Rc;
+use std::time::Instant;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum EngineError {
-    #[error("Version mismatch: Engine({0}), Nodes({1})")]
-    VersionMismatch(String, String),
+    #[error("Version mismatch: Engine({0}), Nodes({1}): {2}")]
+    VersionMismatch(String, String, String),
     #[error(transparent)]
     WorkerError(WorkerError),
     #[error(transparent)]
+    NodeErrors(ErrorReport),
+    #[error("Run {0} was cancelled")]
+    Cancelled(u64),
+    #[error("Start node {0} is not in the graph")]
+    StartNodeNotFound(i64),
+    #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+/// Failures collected by [`Engine::process_all`], keyed by node id
+#[derive(Debug, Default)]
+pub struct ErrorReport {
+    pub errors: BTreeMap<i64, anyhow::Error>,
+    pub skipped: BTreeSet<i64>,
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} node(s) failed", self.errors.len())?;
+        for (id, error) in &self.errors {
+            write!(f, "\n  Node[{}]: {}", id, error)?;
+        }
+        if !self.skipped.is_empty() {
+            write!(f, "\n  Skipped: {:?}", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ErrorReport {}
+
+pub struct ProcessReport {
+    /// Last node reached while processing, same node whose output `Engine::process` returns
+    pub end_id: i64,
+    pub outputs: HashMap<i64, OutputData>,
+    pub disabled: Vec<i64>,
+    pub errors: BTreeMap<i64, anyhow::Error>,
+    /// Nodes that did not run because an upstream node failed or was skipped
+    pub skipped: BTreeSet<i64>,
+}
+
+impl ProcessReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn output(&self) -> Option<&OutputData> {
+        self.outputs.get(&self.end_id)
+    }
+
+    pub fn into_result(self) -> Result<OutputData, EngineError> {
+        if self.errors.is_empty() {
+            if let Some(output) = self.outputs.get(&self.end_id) {
+                return Ok(output.0.clone().into());
+            }
+        }
+        Err(EngineError::NodeErrors(ErrorReport {
+            errors: self.errors,
+            skipped: self.skipped,
+        }))
+    }
+}
+
+enum ErrorMode {
+    FailFast,
+    Collect,
+}
+
+struct Run {
+    mode: ErrorMode,
+    context: RunContext,
+    cache: HashMap<i64, OutputData>,
+    closed_nodes: Vec<i64>,
+    errors: BTreeMap<i64, anyhow::Error>,
+    skipped: BTreeSet<i64>,
+}
+
+impl Run {
+    fn new(mode: ErrorMode, context: RunContext) -> Run {
+        Run {
+            mode,
+            context,
+            cache: HashMap::new(),
+            closed_nodes: Vec::new(),
+            errors: BTreeMap::new(),
+            skipped: BTreeSet::new(),
+        }
+    }
+
+    fn is_blocked(&self, id: i64) -> bool {
+        self.errors.contains_key(&id) || self.skipped.contains(&id)
+    }
+}
+
 pub struct Engine<'a> {
     id: &'a str,
     workers: Workers,
+    compatibility: Compatibility,
+    migrations: Migrations,
+    conversions: Rc<Conversions>,
+    extensions: Extensions,
+    state: Box<dyn StateStore>,
+    logger: Option<Logger>,
+    observer: Option<Observer>,
 }
 
 #[allow(dead_code)]
 impl<'a> Engine<'a> {
     pub fn new(id: &'a str, workers: Workers) -> Engine<'a> {
-        Engine { id, workers }
+        Engine {
+            id,
+            workers,
+            compatibility: Compatibility::Exact,
+            migrations: Migrations::new(),
+            conversions: Rc::default(),
+            extensions: Extensions::default(),
+            state: Box::new(MemoryStateStore::new()),
+            logger: None,
+            observer: None,
+        }
+    }
+
+    pub fn workers(&self) -> &Workers {
+        &self.workers
+    }
+
+    /// Shared services available to workers through `Context::extension`
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Store used for per node state, defaults to a `MemoryStateStore`
+    pub fn set_state_store<S>(&mut self, state: S)
+    where
+        S: StateStore + 'static,
+    {
+        self.state = Box::new(state);
+    }
+
+    pub fn state(&self) -> &dyn StateStore {
+        self.state.as_ref()
+    }
+
+    pub fn reset_node_state(&self, graph_id: &str, node_id: i64) -> Result<()> {
+        self.state.reset(graph_id, node_id)
+    }
+
+    pub fn reset_graph_state(&self, graph_id: &str) -> Result<()> {
+        self.state.reset_graph(graph_id)
+    }
+
+    /// Drops the node state of every graph run on this engine
+    pub fn reset_state(&self) -> Result<()> {
+        self.state.clear()
+    }
+
+    /// Captures the nodes together with the state stored under `graph_id`
+    pub fn snapshot(&self, graph_id: &str, nodes: &HashMap<i64, Node>) -> Result<Snapshot> {
+        Ok(Snapshot {
+            id: self.id.to_string(),
+            graph_id: graph_id.to_string(),
+            nodes: nodes.clone(),
+            state: self.state.snapshot(graph_id)?,
+        })
+    }
+
+    /// Replaces the state of the snapshot's graph and hands back its nodes, the state of other
+    /// graphs is kept
+    pub fn restore(&self, snapshot: Snapshot) -> Result<HashMap<i64, Node>> {
+        self.check_version(&snapshot.id)?;
+        self.state.restore(&snapshot.graph_id, snapshot.state)?;
+        Ok(snapshot.nodes)
+    }
+
+    pub fn set_logger<F>(&mut self, logger: F)
+    where
+        F: Fn(&LogRecord) + 'static,
+    {
+        self.logger = Some(Rc::new(logger));
+    }
+
+    /// Called before and after every worker call, for tracing and progress reporting
+    pub fn set_observer<F>(&mut self, observer: F)
+    where
+        F: Fn(&NodeEvent) + 'static,
+    {
+        self.observer = Some(Rc::new(observer));
+    }
+
+    fn notify(&self, event: NodeEvent) {
+        if let Some(observer) = &self.observer {
+            observer(&event);
+        }
+    }
+
+    /// Which graph versions `parse_value` accepts, defaults to `Compatibility::Exact`
+    pub fn set_compatibility(&mut self, compatibility: Compatibility) {
+        self.compatibility = compatibility;
+    }
+
+    /// Migrations `parse_value` runs before checking the version
+    pub fn add_migration(&mut self, migration: Migration) -> &mut Self {
+        self.migrations.add(migration);
+        self
+    }
+
+    /// Checks the version, the connections and that every node has a worker
+    pub fn validate(&self, graph: &Graph) -> Result<()> {
+        self.check_version(&graph.id)?;
+        graph.validate()?;
+        let mut unknown = graph
+            .nodes
+            .values()
+            .filter(|node| !self.workers.contains(&node.name))
+            .map(|node| (node.id, node.name.as_str()))
+            .collect::<Vec<_>>();
+        unknown.sort_unstable();
+        if let Some((id, name)) = unknown.first() {
+            bail!(WorkerError::NodeRunError(
+                *id,
+                anyhow!(WorkerError::WorkerNotFound(name.to_string()))
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_version(&self, version: &str) -> Result<(), EngineError> {
+        self.compatibility
+            .check(self.id, version)
+            .map_err(|reason| {
+                EngineError::VersionMismatch(self.id.to_string(), version.to_string(), reason)
+            })
+    }
+
+    /// Converters used by the node getters, starts with the `Conversions::default()` set
+    pub fn conversions_mut(&mut self) -> &mut Conversions {
+        Rc::make_mut(&mut self.conversions)
     }
 
     pub fn parse_json(&self, json: &str) -> Result<HashMap<i64, Node>> {
@@ -33,101 +268,281 @@ impl<'a> Engine<'a> {
     }
 
     pub fn parse_value(&self, value: Value) -> Result<HashMap<i64, Node>> {
+        Ok(self.parse_graph(value)?.nodes)
+    }
+
+    pub fn parse_graph_json(&self, json: &str) -> Result<Graph> {
+        let value: Value = serde_json::from_str(json)?;
+        self.parse_graph(value)
+    }
+
+    /// Reads a graph file in any `GraphFormat`
+    pub fn load_graph<P: AsRef<Path>>(&self, path: P) -> Result<Graph> {
+        self.parse_graph(read_value(path.as_ref())?)
+    }
+
+    /// Loads a graph written by `Graph::to_binary`, falls back to json when migrations apply
+    #[cfg(feature = "binary")]
+    pub fn parse_binary(&self, bytes: &[u8]) -> Result<Graph> {
+        let graph = Graph::from_binary(bytes)?;
+        if self.migrations.has_migration(&graph.id) {
+            return self.parse_graph(graph.to_value()?);
+        }
+        self.check_version(&graph.id)?;
+        Ok(graph)
+    }
+
+    /// Like `parse_value` but keeps the whole editor document
+    pub fn parse_graph(&self, value: Value) -> Result<Graph> {
+        Ok(self.parse_graph_migrated(value)?.0)
+    }
+
+    /// Also reports which migrations were applied
+    pub fn parse_graph_migrated(&self, value: Value) -> Result<(Graph, Vec<AppliedMigration>)> {
+        let (value, applied) = self.migrations.migrate(value)?;
         let version = value["id"]
             .as_str()
             .ok_or(anyhow!("Engine has no version"))?
             .to_string();
-        if self.id != version {
-            bail!(EngineError::VersionMismatch(self.id.to_string(), version));
-        }
-        let nodess: HashMap<String, Node> = serde_json::from_value(value["nodes"].clone())?;
-        nodess
-            .into_iter()
-            .map(|(k, v)| Ok((k.parse::<i64>()?, v)))
-            .collect::<Result<HashMap<_, _>>>()
+        self.check_version(&version)?;
+        Ok((Graph::from_value(value)?, applied))
+    }
+
+    pub fn process(&self, nodes: &HashMap<i64, Node>, start_node_id: i64) -> Result<OutputData> {
+        self.process_with(nodes, start_node_id, RunContext::new())
+    }
+
+    pub fn process_with(
+        &self,
+        nodes: &HashMap<i64, Node>,
+        start_node_id: i64,
+        context: RunContext,
+    ) -> Result<OutputData> {
+        let start = nodes
+            .get(&start_node_id)
+            .ok_or(EngineError::StartNodeNotFound(start_node_id))?;
+        self.workers.before_run(&context)?;
+        let mut run = Run::new(ErrorMode::FailFast, context);
+        let result = self.process_nodes(start, nodes, &mut run);
+        let after = self.workers.after_run(&run.context);
+        let end_id = result?;
+        after?;
+        Ok(run.cache[&end_id].clone().into())
+    }
+
+    /// Runs only the nodes of the group
+    pub fn process_group(
+        &self,
+        graph: &Graph,
+        group_id: i64,
+        start_node_id: i64,
+    ) -> Result<OutputData> {
+        self.process_group_with(graph, group_id, start_node_id, RunContext::new())
+    }
+
+    pub fn process_group_with(
+        &self,
+        graph: &Graph,
+        group_id: i64,
+        start_node_id: i64,
+        context: RunContext,
+    ) -> Result<OutputData> {
+        self.process_with(&graph.only_group(group_id).nodes, start_node_id, context)
+    }
+
+    /// Runs everything except the nodes of the group
+    pub fn process_without_group(
+        &self,
+        graph: &Graph,
+        group_id: i64,
+        start_node_id: i64,
+    ) -> Result<OutputData> {
+        self.process_without_group_with(graph, group_id, start_node_id, RunContext::new())
+    }
+
+    pub fn process_without_group_with(
+        &self,
+        graph: &Graph,
+        group_id: i64,
+        start_node_id: i64,
+        context: RunContext,
+    ) -> Result<OutputData> {
+        self.process_with(&graph.without_group(group_id).nodes, start_node_id, context)
+    }
+
+    /// Keeps running independent branches after a worker fails, nodes that depend on a
+    /// failed node are skipped and every failure is collected in the returned report
+    pub fn process_all(&self, nodes: &HashMap<i64, Node>, start_node_id: i64) -> ProcessReport {
+        self.process_all_with(nodes, start_node_id, RunContext::new())
     }
 
-    /// Consumes engine
-    pub fn process(self, nodes: &'a HashMap<i64, Node>, start_node_id: i64) -> Result<OutputData> {
-        let mut cache: HashMap<i64, OutputData> = HashMap::new();
-        let mut closed_nodes: Vec<i64> = Vec::new();
-        let end_id =
-            self.process_nodes(&nodes[&start_node_id], nodes, &mut cache, &mut closed_nodes)?;
-        Ok(cache[&end_id].clone().into())
+    pub fn process_all_with(
+        &self,
+        nodes: &HashMap<i64, Node>,
+        start_node_id: i64,
+        context: RunContext,
+    ) -> ProcessReport {
+        let mut run = Run::new(ErrorMode::Collect, context);
+        let mut after = Ok(());
+        let result = nodes
+            .get(&start_node_id)
+            .ok_or(EngineError::StartNodeNotFound(start_node_id))
+            .and_then(|start| {
+                self.workers
+                    .before_run(&run.context)
+                    .map_err(EngineError::from)?;
+                let result = self.process_nodes(start, nodes, &mut run);
+                after = self.workers.after_run(&run.context);
+                result
+            });
+        let end_id = match result {
+            Ok(id) => id,
+            Err(e) => {
+                run.errors.insert(start_node_id, e.into());
+                start_node_id
+            }
+        };
+        if let Err(e) = after {
+            run.errors.entry(start_node_id).or_insert(e);
+        }
+        ProcessReport {
+            end_id,
+            outputs: run.cache,
+            disabled: run.closed_nodes,
+            errors: run.errors,
+            skipped: run.skipped,
+        }
     }
 
     fn process_node(
         &self,
-        node: &'a Node,
-        nodes: &'a HashMap<i64, Node>,
-        cache: &mut HashMap<i64, OutputData>,
-        closed_nodes: &mut Vec<i64>,
+        node: &Node,
+        nodes: &HashMap<i64, Node>,
+        run: &mut Run,
     ) -> Result<OutputData, EngineError> {
-        if cache.contains_key(&node.id) {
-            return Ok(cache[&node.id].clone().into());
+        if run.cache.contains_key(&node.id) {
+            return Ok(run.cache[&node.id].clone().into());
         }
-        if closed_nodes.contains(&node.id) {
+        if run.closed_nodes.contains(&node.id) || run.is_blocked(node.id) {
             return Ok(Rc::new(HashMap::new()).into());
         }
 
         let mut input_data: Vec<(String, OutputData)> = vec![];
         for (name, input) in node.inputs.clone().unwrap_or_default().inner() {
             for conn in &input.connections {
-                if !closed_nodes.contains(&conn.node) {
-                    let out = self.process_node(&nodes[&conn.node], nodes, cache, closed_nodes)?;
+                if !run.closed_nodes.contains(&conn.node) {
+                    let out = self.process_node(&nodes[&conn.node], nodes, run)?;
+                    if run.is_blocked(conn.node) {
+                        if run.skipped.insert(node.id) {
+                            self.notify(NodeEvent::Skipped {
+                                run_id: run.context.run_id,
+                                node_id: node.id,
+                            });
+                        }
+                        return Ok(Rc::new(HashMap::new()).into());
+                    }
                     input_data.push((name.clone(), out.clone().into()));
                     if !out.clone().contains_key(&conn.output) && conn.output != "action" {
-                        self.disable_node_tree(&nodes[&conn.node], nodes, closed_nodes);
-                        self.disable_node_tree(node, nodes, closed_nodes);
+                        self.disable_node_tree(&nodes[&conn.node], nodes, &mut run.closed_nodes);
+                        self.disable_node_tree(node, nodes, &mut run.closed_nodes);
                     }
                 }
             }
         }
         let mut output = Rc::new(HashMap::new()).into();
-        if !closed_nodes.contains(&node.id) {
-            output = self.workers.call(
+        if !run.closed_nodes.contains(&node.id) {
+            if run.context.cancellation.is_cancelled() {
+                return Err(EngineError::Cancelled(run.context.run_id));
+            }
+            let context = Context::new(
+                &run.context,
+                &self.extensions,
+                self.state.as_ref(),
+                self.logger.as_ref(),
+                node.id,
+                &node.name,
+            );
+            self.notify(NodeEvent::Started {
+                run_id: run.context.run_id,
+                node_id: node.id,
+                worker: &node.name,
+            });
+            let started = Instant::now();
+            let result = self.workers.call_with_context(
                 &node.name,
                 node,
                 input_data
                     .into_iter()
-                    .fold(InputDataBuilder::new(), |b, (key, data)| {
-                        b.add_data(key, data)
-                    })
+                    .fold(
+                        InputDataBuilder::new().conversions(self.conversions.clone()),
+                        |b, (key, data)| b.add_data(key, data),
+                    )
                     .build(),
-            )?;
-            cache.insert(node.id, output.clone().into());
+                &context,
+            );
+            match &result {
+                Ok(outputs) => self.notify(NodeEvent::Finished {
+                    run_id: run.context.run_id,
+                    node_id: node.id,
+                    worker: &node.name,
+                    outputs,
+                    elapsed: started.elapsed(),
+                }),
+                Err(error) => self.notify(NodeEvent::Failed {
+                    run_id: run.context.run_id,
+                    node_id: node.id,
+                    worker: &node.name,
+                    error,
+                    elapsed: started.elapsed(),
+                }),
+            }
+            match (result, &run.mode) {
+                (Ok(out), _) => output = out,
+                (Err(e), ErrorMode::FailFast) => return Err(e.into()),
+                (Err(e), ErrorMode::Collect) => {
+                    run.errors.insert(node.id, e);
+                    return Ok(output);
+                }
+            }
+            run.cache.insert(node.id, output.clone().into());
         }
         Ok(output)
     }
 
     fn process_nodes(
         &self,
-        node: &'a Node,
-        nodes: &'a HashMap<i64, Node>,
-        cache: &mut HashMap<i64, OutputData>,
-        closed_nodes: &mut Vec<i64>,
+        node: &Node,
+        nodes: &HashMap<i64, Node>,
+        run: &mut Run,
     ) -> Result<i64, EngineError> {
         let mut id: i64 = node.id;
-        if !closed_nodes.contains(&node.id) {
-            let outputdata = self.process_node(node, nodes, cache, closed_nodes)?;
+        if !run.closed_nodes.contains(&node.id) {
+            let outputdata = self.process_node(node, nodes, run)?;
             for (name, output) in node.outputs.clone().unwrap_or_default().inner() {
-                if outputdata.contains_key(name) {
+                if run.is_blocked(node.id) {
                     for connection in &output.connections {
-                        if !closed_nodes.contains(&connection.node) {
-                            id = self.process_nodes(
-                                &nodes[&connection.node],
-                                nodes,
-                                cache,
-                                closed_nodes,
-                            )?;
+                        if !run.closed_nodes.contains(&connection.node)
+                            && !run.is_blocked(connection.node)
+                        {
+                            id = self.process_nodes(&nodes[&connection.node], nodes, run)?;
+                        }
+                    }
+                } else if outputdata.contains_key(name) {
+                    for connection in &output.connections {
+                        if !run.closed_nodes.contains(&connection.node) {
+                            id = self.process_nodes(&nodes[&connection.node], nodes, run)?;
                         }
                     }
                 } else if name != "action" {
                     for connection in &output.connections {
                         if connection.input == name.clone()
-                            && !closed_nodes.contains(&connection.node)
+                            && !run.closed_nodes.contains(&connection.node)
                         {
-                            self.disable_node_tree(&nodes[&connection.node], nodes, closed_nodes);
+                            self.disable_node_tree(
+                                &nodes[&connection.node],
+                                nodes,
+                                &mut run.closed_nodes,
+                            );
                         }
                     }
                 }
@@ -144,42 +559,38 @@ impl<'a> Engine<'a> {
         closed_nodes: &mut Vec<i64>,
     ) {
         match node.inputs.clone().unwrap_or_default().get("action") {
-            None => (),
-            Some(input) => {
-                if input.connections.len() == 1 {
-                    if !closed_nodes.contains(&node.id) {
-                        closed_nodes.push(node.id);
-                    }
-                    node.outputs
-                        .clone()
-                        .unwrap_or_default()
-                        .inner()
-                        .iter()
-                        .for_each(|(_, output)| {
-                            for connection in &output.connections {
-                                let _node = &nodes[&connection.node];
-                                match _node.inputs.clone().unwrap_or_default().get("action") {
-                                    None => (),
-                                    Some(input) => {
-                                        if input
-                                            .connections
-                                            .clone()
-                                            .into_iter()
-                                            .any(|c| c.node == connection.node)
-                                        {
-                                            self.disable_node_tree(
-                                                &nodes[&connection.node],
-                                                nodes,
-                                                closed_nodes,
-                                            );
-                                        }
-                                    }
+            Some(input) if input.connections.len() == 1 => {
+                if !closed_nodes.contains(&node.id) {
+                    closed_nodes.push(node.id);
+                }
+                node.outputs
+                    .clone()
+                    .unwrap_or_default()
+                    .inner()
+                    .iter()
+                    .for_each(|(_, output)| {
+                        for connection in &output.connections {
+                            let _node = &nodes[&connection.node];
+                            match _node.inputs.clone().unwrap_or_default().get("action") {
+                                Some(input)
+                                    if input
+                                        .connections
+                                        .clone()
+                                        .into_iter()
+                                        .any(|c| c.node == connection.node) =>
+                                {
+                                    self.disable_node_tree(
+                                        &nodes[&connection.node],
+                                        nodes,
+                                        closed_nodes,
+                                    );
                                 }
+                                _ => (),
                             }
-                        });
-                }
+                        }
+                    });
             }
+            _ => (),
         }
     }
 }
-
@@ -0,0 +1,208 @@
+use crate::engine::ProcessReport;
+use crate::node::Node;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Connections between `action` sockets carry control flow instead of data
+const ACTION: &str = "action";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeStatus {
+    Executed,
+    Disabled,
+    Failed,
+    Skipped,
+}
+
+impl NodeStatus {
+    fn class(&self) -> &'static str {
+        match self {
+            NodeStatus::Executed => "executed",
+            NodeStatus::Disabled => "disabled",
+            NodeStatus::Failed => "failed",
+            NodeStatus::Skipped => "skipped",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            NodeStatus::Executed => "#c8e6c9",
+            NodeStatus::Disabled => "#e0e0e0",
+            NodeStatus::Failed => "#ffcdd2",
+            NodeStatus::Skipped => "#ffe0b2",
+        }
+    }
+}
+
+const STATUSES: [NodeStatus; 4] = [
+    NodeStatus::Executed,
+    NodeStatus::Disabled,
+    NodeStatus::Failed,
+    NodeStatus::Skipped,
+];
+
+/// Results of a run drawn on top of an exported graph
+#[derive(Clone, Debug, Default)]
+pub struct ExecutionOverlay(BTreeMap<i64, NodeStatus>);
+
+impl ExecutionOverlay {
+    pub fn new() -> ExecutionOverlay {
+        ExecutionOverlay::default()
+    }
+
+    pub fn set(&mut self, node_id: i64, status: NodeStatus) -> &mut Self {
+        self.0.insert(node_id, status);
+        self
+    }
+
+    pub fn status(&self, node_id: i64) -> Option<NodeStatus> {
+        self.0.get(&node_id).copied()
+    }
+}
+
+impl From<&ProcessReport> for ExecutionOverlay {
+    fn from(report: &ProcessReport) -> Self {
+        let mut overlay = ExecutionOverlay::new();
+        report.outputs.keys().for_each(|id| {
+            overlay.set(*id, NodeStatus::Executed);
+        });
+        report.disabled.iter().for_each(|id| {
+            overlay.set(*id, NodeStatus::Disabled);
+        });
+        report.skipped.iter().for_each(|id| {
+            overlay.set(*id, NodeStatus::Skipped);
+        });
+        report.errors.keys().for_each(|id| {
+            overlay.set(*id, NodeStatus::Failed);
+        });
+        overlay
+    }
+}
+
+struct Edge<'a> {
+    from: i64,
+    output: &'a str,
+    to: i64,
+    input: &'a str,
+}
+
+impl Edge<'_> {
+    fn is_control(&self) -> bool {
+        self.output == ACTION || self.input == ACTION
+    }
+
+    fn label(&self) -> String {
+        format!("{} → {}", self.output, self.input)
+    }
+}
+
+fn sorted_nodes(nodes: &HashMap<i64, Node>) -> Vec<&Node> {
+    let mut sorted = nodes.values().collect::<Vec<_>>();
+    sorted.sort_by_key(|node| node.id);
+    sorted
+}
+
+fn edges<'a>(nodes: &[&'a Node]) -> Vec<Edge<'a>> {
+    nodes
+        .iter()
+        .flat_map(|node| {
+            let mut outputs = node
+                .outputs
+                .iter()
+                .flat_map(|outputs| outputs.inner())
+                .collect::<Vec<_>>();
+            outputs.sort_by_key(|(name, _)| *name);
+            outputs.into_iter().flat_map(move |(name, output)| {
+                output.connections.iter().map(move |connection| Edge {
+                    from: node.id,
+                    output: name,
+                    to: connection.node,
+                    input: &connection.input,
+                })
+            })
+        })
+        .collect()
+}
+
+fn label(node: &Node) -> String {
+    format!("{}: {}", node.id, node.name)
+}
+
+/// Graphviz DOT, control flow edges are dashed
+pub fn to_dot(nodes: &HashMap<i64, Node>, overlay: Option<&ExecutionOverlay>) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let nodes = sorted_nodes(nodes);
+    let mut dot = String::from("digraph {\n  rankdir=LR;\n  node [shape=box];\n");
+    for node in &nodes {
+        let _ = write!(dot, "  n{} [label=\"{}\"", node.id, escape(&label(node)));
+        if let Some(status) = overlay.and_then(|o| o.status(node.id)) {
+            let _ = write!(
+                dot,
+                ", style=filled, fillcolor=\"{}\", tooltip=\"{}\"",
+                status.color(),
+                status.class()
+            );
+        }
+        dot.push_str("];\n");
+    }
+    for edge in edges(&nodes) {
+        let _ = write!(
+            dot,
+            "  n{} -> n{} [label=\"{}\"",
+            edge.from,
+            edge.to,
+            escape(&edge.label())
+        );
+        if edge.is_control() {
+            dot.push_str(", style=dashed, color=blue");
+        }
+        dot.push_str("];\n");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Mermaid flowchart, control flow edges are dotted
+pub fn to_mermaid(nodes: &HashMap<i64, Node>, overlay: Option<&ExecutionOverlay>) -> String {
+    let escape = |s: &str| s.replace('"', "#quot;");
+    let nodes = sorted_nodes(nodes);
+    let mut mermaid = String::from("flowchart LR\n");
+    for node in &nodes {
+        let _ = writeln!(mermaid, "  n{}[\"{}\"]", node.id, escape(&label(node)));
+    }
+    for edge in edges(&nodes) {
+        let arrow = if edge.is_control() {
+            ("-.", ".->")
+        } else {
+            ("--", "-->")
+        };
+        let _ = writeln!(
+            mermaid,
+            "  n{} {} \"{}\" {} n{}",
+            edge.from,
+            arrow.0,
+            escape(&edge.label()),
+            arrow.1,
+            edge.to
+        );
+    }
+    if let Some(overlay) = overlay {
+        for status in STATUSES {
+            let members = nodes
+                .iter()
+                .filter(|node| overlay.status(node.id) == Some(status))
+                .map(|node| format!("n{}", node.id))
+                .collect::<Vec<_>>();
+            if !members.is_empty() {
+                let _ = writeln!(
+                    mermaid,
+                    "  classDef {} fill:{}",
+                    status.class(),
+                    status.color()
+                );
+                let _ = writeln!(mermaid, "  class {} {}", members.join(","), status.class());
+            }
+        }
+    }
+    mermaid
+}
@@ -0,0 +1,662 @@
+use anyhow::Result;
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// `position` counts characters from the start of the expression
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("Expression syntax error at {position}: {message}")]
+pub struct ExpressionError {
+    pub position: usize,
+    pub message: String,
+}
+
+const FUNCTIONS: &[&str] = &["min", "max", "abs", "floor", "ceil", "round", "len", "str"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Var(String),
+    Array(Vec<Expr>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    /// Operators of one precedence level applied left to right, kept flat so that long
+    /// chains don't nest
+    Binary(Box<Expr>, Vec<(BinOp, Expr)>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// Small expression language over json values, e.g. `num * 2 + num2` or `user.age >= 18 ? "adult" : "minor"`
+///
+/// Supports arithmetic, comparison, `&&`, `||`, `!`, the ternary operator, member and index
+/// access, array literals and the functions `min`, `max`, `abs`, `floor`, `ceil`, `round`,
+/// `len` and `str`. Integers stay integers until they meet a float.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Expr,
+}
+
+impl Expression {
+    /// Deepest nesting of parentheses, unary and ternary operators, calls and member accesses
+    /// `parse` accepts, chains like `1 + 2 + 3` don't nest
+    pub const MAX_DEPTH: usize = 64;
+
+    pub fn parse(source: &str) -> Result<Expression, ExpressionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let root = parser.expr()?;
+        match parser.peek() {
+            (Token::End, _) => Ok(Expression {
+                source: source.to_string(),
+                root,
+            }),
+            (token, position) => Err(ExpressionError {
+                position: *position,
+                message: format!("unexpected {}", token),
+            }),
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Names of the variables the expression reads
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = vec![];
+        collect_vars(&self.root, &mut names);
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    pub fn eval(&self, vars: &HashMap<String, Value>) -> Result<Value> {
+        eval(&self.root, vars)
+    }
+}
+
+fn collect_vars<'e>(expr: &'e Expr, names: &mut Vec<&'e str>) {
+    match expr {
+        Expr::Literal(_) => {}
+        Expr::Var(name) => names.push(name),
+        Expr::Array(items) | Expr::Call(_, items) => {
+            items.iter().for_each(|item| collect_vars(item, names))
+        }
+        Expr::Neg(e) | Expr::Not(e) | Expr::Member(e, _) => collect_vars(e, names),
+        Expr::Binary(first, rest) => {
+            collect_vars(first, names);
+            rest.iter().for_each(|(_, e)| collect_vars(e, names))
+        }
+        Expr::Index(a, b) => {
+            collect_vars(a, names);
+            collect_vars(b, names);
+        }
+        Expr::Ternary(c, a, b) => {
+            collect_vars(c, names);
+            collect_vars(a, names);
+            collect_vars(b, names);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Literal(Value),
+    Ident(String),
+    Op(&'static str),
+    End,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Literal(value) => write!(f, "`{}`", value),
+            Token::Ident(name) => write!(f, "`{}`", name),
+            Token::Op(op) => write!(f, "`{}`", op),
+            Token::End => write!(f, "end of expression"),
+        }
+    }
+}
+
+const OPS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!", "?", ":", "(", ")",
+    "[", "]", ".", ",",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            // indices after a `.` never have a fraction, as in `items.0.1`
+            let index = tokens.last().map(|(token, _)| token) == Some(&Token::Op("."));
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                if chars[i] == '.'
+                    && (index || !chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+                {
+                    break;
+                }
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = if text.contains('.') {
+                text.parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+            } else {
+                text.parse::<i64>().ok().map(Value::from)
+            };
+            let value = value.ok_or_else(|| ExpressionError {
+                position: start,
+                message: format!("invalid number `{}`", text),
+            })?;
+            tokens.push((Token::Literal(value), start));
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => {
+                        return Err(ExpressionError {
+                            position: start,
+                            message: "unterminated string".into(),
+                        })
+                    }
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        let escaped = chars.get(i + 1).ok_or_else(|| ExpressionError {
+                            position: i,
+                            message: "unterminated string".into(),
+                        })?;
+                        text.push(match escaped {
+                            'n' => '\n',
+                            't' => '\t',
+                            other => *other,
+                        });
+                        i += 2;
+                    }
+                    Some(other) => {
+                        text.push(*other);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push((Token::Literal(Value::String(text)), start));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let token = match word.as_str() {
+                "true" => Token::Literal(Value::Bool(true)),
+                "false" => Token::Literal(Value::Bool(false)),
+                "null" => Token::Literal(Value::Null),
+                _ => Token::Ident(word),
+            };
+            tokens.push((token, start));
+        } else {
+            let op = OPS
+                .iter()
+                .find(|op| {
+                    op.chars()
+                        .enumerate()
+                        .all(|(n, o)| chars.get(i + n) == Some(&o))
+                })
+                .ok_or_else(|| ExpressionError {
+                    position: start,
+                    message: format!("unexpected character `{}`", c),
+                })?;
+            i += op.len();
+            tokens.push((Token::Op(op), start));
+        }
+    }
+    tokens.push((Token::End, chars.len()));
+    Ok(tokens)
+}
+
+/// `depth` bounds the recursion of the parser and the depth of the tree it builds, so that
+/// neither parsing nor evaluating hostile input overflows the stack
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &(Token, usize) {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.pos].clone();
+        if token.0 != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn enter(&mut self) -> Result<(), ExpressionError> {
+        self.depth += 1;
+        if self.depth > Expression::MAX_DEPTH {
+            return Err(ExpressionError {
+                position: self.peek().1,
+                message: format!(
+                    "expression is nested deeper than {} levels",
+                    Expression::MAX_DEPTH
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        if self.peek().0 == Token::Op(op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<(), ExpressionError> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            let (token, position) = self.peek();
+            Err(ExpressionError {
+                position: *position,
+                message: format!("expected `{}`, found {}", op, token),
+            })
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ExpressionError> {
+        let depth = self.depth;
+        self.enter()?;
+        let mut expr = self.binary(0)?;
+        if self.eat("?") {
+            let then = self.expr()?;
+            self.expect(":")?;
+            let otherwise = self.expr()?;
+            expr = Expr::Ternary(Box::new(expr), Box::new(then), Box::new(otherwise));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    /// Precedence climbing, higher levels bind tighter
+    fn binary(&mut self, level: usize) -> Result<Expr, ExpressionError> {
+        const LEVELS: &[&[(&str, BinOp)]] = &[
+            &[("||", BinOp::Or)],
+            &[("&&", BinOp::And)],
+            &[
+                ("==", BinOp::Eq),
+                ("!=", BinOp::Ne),
+                ("<=", BinOp::Le),
+                (">=", BinOp::Ge),
+                ("<", BinOp::Lt),
+                (">", BinOp::Gt),
+            ],
+            &[("+", BinOp::Add), ("-", BinOp::Sub)],
+            &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Rem)],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let first = self.binary(level + 1)?;
+        let mut rest = vec![];
+        'outer: loop {
+            for (op, bin) in LEVELS[level] {
+                if self.eat(op) {
+                    rest.push((*bin, self.binary(level + 1)?));
+                    continue 'outer;
+                }
+            }
+            return Ok(if rest.is_empty() {
+                first
+            } else {
+                Expr::Binary(Box::new(first), rest)
+            });
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExpressionError> {
+        let depth = self.depth;
+        let expr = if self.eat("-") {
+            self.enter()?;
+            Expr::Neg(Box::new(self.unary()?))
+        } else if self.eat("!") {
+            self.enter()?;
+            Expr::Not(Box::new(self.unary()?))
+        } else {
+            self.postfix()?
+        };
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn postfix(&mut self) -> Result<Expr, ExpressionError> {
+        let depth = self.depth;
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                self.enter()?;
+                match self.next() {
+                    (Token::Ident(name), _) => expr = Expr::Member(Box::new(expr), name),
+                    (Token::Literal(Value::Number(n)), _) if n.is_u64() => {
+                        expr = Expr::Index(Box::new(expr), Box::new(Expr::Literal(n.into())))
+                    }
+                    (token, position) => {
+                        return Err(ExpressionError {
+                            position,
+                            message: format!("expected a field name, found {}", token),
+                        })
+                    }
+                }
+            } else if self.eat("[") {
+                self.enter()?;
+                let index = self.expr()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn list(&mut self, close: &'static str) -> Result<Vec<Expr>, ExpressionError> {
+        let mut items = vec![];
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.expr()?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExpressionError> {
+        match self.next() {
+            (Token::Literal(value), _) => Ok(Expr::Literal(value)),
+            (Token::Ident(name), position) => {
+                if self.eat("(") {
+                    if !FUNCTIONS.contains(&name.as_str()) {
+                        return Err(ExpressionError {
+                            position,
+                            message: format!("unknown function `{}`", name),
+                        });
+                    }
+                    Ok(Expr::Call(name, self.list(")")?))
+                } else {
+                    Ok(Expr::Var(name))
+                }
+            }
+            (Token::Op("("), _) => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            (Token::Op("["), _) => Ok(Expr::Array(self.list("]")?)),
+            (token, position) => Err(ExpressionError {
+                position,
+                message: format!("expected a value, found {}", token),
+            }),
+        }
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn as_bool(value: &Value) -> Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| anyhow!("Expected a bool, got {}", type_name(value)))
+}
+
+fn float(value: f64) -> Result<Value> {
+    Number::from_f64(value)
+        .map(Value::Number)
+        .ok_or_else(|| anyhow!("Result `{}` is not a finite number", value))
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn arithmetic(op: BinOp, a: &Value, b: &Value) -> Result<Value> {
+    if op == BinOp::Add && (a.is_string() || b.is_string()) {
+        return Ok(Value::String(format!("{}{}", text(a), text(b))));
+    }
+    if let (Some(x), Some(y)) = (a.as_i64(), b.as_i64()) {
+        let result = match op {
+            BinOp::Add => x.checked_add(y),
+            BinOp::Sub => x.checked_sub(y),
+            BinOp::Mul => x.checked_mul(y),
+            BinOp::Div if y == 0 => bail!("Division by zero"),
+            BinOp::Div if x.checked_rem(y) == Some(0) => x.checked_div(y),
+            BinOp::Div => return float(x as f64 / y as f64),
+            BinOp::Rem if y == 0 => bail!("Division by zero"),
+            _ => x.checked_rem(y),
+        };
+        return result
+            .map(Value::from)
+            .ok_or_else(|| anyhow!("Integer overflow"));
+    }
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => float(match op {
+            BinOp::Add => x + y,
+            BinOp::Sub => x - y,
+            BinOp::Mul => x * y,
+            BinOp::Div if y == 0.0 => bail!("Division by zero"),
+            BinOp::Div => x / y,
+            _ => x % y,
+        }),
+        _ => bail!(
+            "Can't apply {:?} to {} and {}",
+            op,
+            type_name(a),
+            type_name(b)
+        ),
+    }
+}
+
+fn equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => x == y,
+            _ => x.as_f64() == y.as_f64(),
+        },
+        (a, b) => a == b,
+    }
+}
+
+fn compare(op: BinOp, a: &Value, b: &Value) -> Result<Value> {
+    let ordering = match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => Some(x.cmp(&y)),
+            _ => x.as_f64().partial_cmp(&y.as_f64()),
+        },
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => bail!("Can't compare {} and {}", type_name(a), type_name(b)),
+    };
+    let ordering = ordering.ok_or_else(|| anyhow!("Can't compare {} and {}", a, b))?;
+    Ok(Value::Bool(match op {
+        BinOp::Lt => ordering.is_lt(),
+        BinOp::Le => ordering.is_le(),
+        BinOp::Gt => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    }))
+}
+
+fn call(name: &str, args: Vec<Value>) -> Result<Value> {
+    let number = |value: &Value| {
+        value
+            .as_f64()
+            .ok_or_else(|| anyhow!("{}() expects numbers, got {}", name, type_name(value)))
+    };
+    match (name, args.as_slice()) {
+        ("min" | "max", [first, rest @ ..]) => {
+            let mut best = first;
+            for arg in rest {
+                let (a, b) = (number(best)?, number(arg)?);
+                if (name == "min" && b < a) || (name == "max" && b > a) {
+                    best = arg;
+                }
+            }
+            number(best)?;
+            Ok(best.clone())
+        }
+        ("abs", [value]) => match value.as_i64() {
+            Some(i) => i
+                .checked_abs()
+                .map(Value::from)
+                .ok_or_else(|| anyhow!("Integer overflow")),
+            None => float(number(value)?.abs()),
+        },
+        ("floor" | "ceil" | "round", [value]) => {
+            if value.is_i64() {
+                return Ok(value.clone());
+            }
+            let x = number(value)?;
+            let x = match name {
+                "floor" => x.floor(),
+                "ceil" => x.ceil(),
+                _ => x.round(),
+            };
+            if x.abs() < i64::MAX as f64 {
+                Ok(Value::from(x as i64))
+            } else {
+                float(x)
+            }
+        }
+        ("len", [value]) => match value {
+            Value::String(s) => Ok(Value::from(s.chars().count())),
+            Value::Array(a) => Ok(Value::from(a.len())),
+            Value::Object(o) => Ok(Value::from(o.len())),
+            other => bail!(
+                "len() expects a string, array or object, got {}",
+                type_name(other)
+            ),
+        },
+        ("str", [value]) => Ok(Value::String(text(value))),
+        _ => bail!("{}() does not take {} arguments", name, args.len()),
+    }
+}
+
+fn eval(expr: &Expr, vars: &HashMap<String, Value>) -> Result<Value> {
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Var(name) => vars
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unknown variable `{}`", name)),
+        Expr::Array(items) => Ok(Value::Array(
+            items
+                .iter()
+                .map(|item| eval(item, vars))
+                .collect::<Result<_>>()?,
+        )),
+        Expr::Neg(e) => arithmetic(BinOp::Sub, &Value::from(0), &eval(e, vars)?),
+        Expr::Not(e) => Ok(Value::Bool(!as_bool(&eval(e, vars)?)?)),
+        Expr::Binary(first, rest) => {
+            let mut a = eval(first, vars)?;
+            for (op, b) in rest {
+                a = match op {
+                    BinOp::And => Value::Bool(as_bool(&a)? && as_bool(&eval(b, vars)?)?),
+                    BinOp::Or => Value::Bool(as_bool(&a)? || as_bool(&eval(b, vars)?)?),
+                    op => {
+                        let b = eval(b, vars)?;
+                        match op {
+                            BinOp::Eq => Value::Bool(equals(&a, &b)),
+                            BinOp::Ne => Value::Bool(!equals(&a, &b)),
+                            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => compare(*op, &a, &b)?,
+                            _ => arithmetic(*op, &a, &b)?,
+                        }
+                    }
+                };
+            }
+            Ok(a)
+        }
+        Expr::Ternary(condition, then, otherwise) => {
+            if as_bool(&eval(condition, vars)?)? {
+                eval(then, vars)
+            } else {
+                eval(otherwise, vars)
+            }
+        }
+        Expr::Member(e, name) => Ok(eval(e, vars)?.get(name).cloned().unwrap_or(Value::Null)),
+        Expr::Index(e, index) => {
+            let value = eval(e, vars)?;
+            let index = eval(index, vars)?;
+            Ok(match (&value, &index) {
+                (Value::Array(a), Value::Number(n)) => n
+                    .as_u64()
+                    .and_then(|i| a.get(i as usize))
+                    .cloned()
+                    .unwrap_or(Value::Null),
+                (Value::Object(o), Value::String(key)) => {
+                    o.get(key).cloned().unwrap_or(Value::Null)
+                }
+                _ => bail!(
+                    "Can't index {} with {}",
+                    type_name(&value),
+                    type_name(&index)
+                ),
+            })
+        }
+        Expr::Call(name, args) => call(
+            name,
+            args.iter()
+                .map(|arg| eval(arg, vars))
+                .collect::<Result<_>>()?,
+        ),
+    }
+}
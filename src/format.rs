@@ -0,0 +1,109 @@
+use crate::graph::Graph;
+use anyhow::Result;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// File formats a graph can be stored in, all of them convert losslessly to the editor json
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    Json,
+    Yaml,
+    Ron,
+}
+
+impl GraphFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<GraphFormat> {
+        match path.as_ref().extension()?.to_str()? {
+            "json" => Some(GraphFormat::Json),
+            "yaml" | "yml" => Some(GraphFormat::Yaml),
+            "ron" => Some(GraphFormat::Ron),
+            _ => None,
+        }
+    }
+
+    /// Json is tried first since every json document is also valid yaml
+    pub fn detect(content: &str) -> GraphFormat {
+        if serde_json::from_str::<Value>(content).is_ok() {
+            GraphFormat::Json
+        } else if content.trim_start().starts_with(['{', '(']) {
+            GraphFormat::Ron
+        } else {
+            GraphFormat::Yaml
+        }
+    }
+
+    pub fn parse(&self, content: &str) -> Result<Value> {
+        match self {
+            GraphFormat::Json => Ok(serde_json::from_str(content)?),
+            #[cfg(feature = "yaml")]
+            GraphFormat::Yaml => Ok(serde_yaml::from_str(content)?),
+            #[cfg(feature = "ron")]
+            GraphFormat::Ron => Ok(ron::from_str(content)?),
+            #[allow(unreachable_patterns)]
+            format => bail!(
+                "{:?} support requires the `{}` feature",
+                format,
+                format.feature()
+            ),
+        }
+    }
+
+    /// Pretty printed with sorted keys so diffs stay readable
+    pub fn write(&self, value: &Value) -> Result<String> {
+        match self {
+            GraphFormat::Json => Ok(serde_json::to_string_pretty(value)? + "\n"),
+            #[cfg(feature = "yaml")]
+            GraphFormat::Yaml => Ok(serde_yaml::to_string(value)?),
+            #[cfg(feature = "ron")]
+            GraphFormat::Ron => {
+                let config = ron::ser::PrettyConfig::new().indentor("  ".to_string());
+                Ok(ron::ser::to_string_pretty(value, config)? + "\n")
+            }
+            #[allow(unreachable_patterns)]
+            format => bail!(
+                "{:?} support requires the `{}` feature",
+                format,
+                format.feature()
+            ),
+        }
+    }
+
+    #[allow(dead_code)]
+    fn feature(&self) -> &'static str {
+        match self {
+            GraphFormat::Json => "default",
+            GraphFormat::Yaml => "yaml",
+            GraphFormat::Ron => "ron",
+        }
+    }
+}
+
+impl Graph {
+    pub fn from_format(content: &str, format: GraphFormat) -> Result<Graph> {
+        Graph::from_value(format.parse(content)?)
+    }
+
+    pub fn to_format(&self, format: GraphFormat) -> Result<String> {
+        format.write(&self.to_value()?)
+    }
+
+    /// Picks the format from the file extension and falls back to the content
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Graph> {
+        Graph::from_value(read_value(path.as_ref())?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let format = GraphFormat::from_path(&path).unwrap_or(GraphFormat::Json);
+        fs::write(path, self.to_format(format)?)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn read_value(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path)?;
+    let format = GraphFormat::from_path(path).unwrap_or_else(|| GraphFormat::detect(&content));
+    format
+        .parse(&content)
+        .map_err(|e| anyhow!("{}: {}", path.display(), e))
+}
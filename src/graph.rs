@@ -0,0 +1,509 @@
+use crate::comment::Comment;
+use crate::group::{Group, Groups};
+use crate::node::Node;
+use crate::target::{Input, InputConnection, Inputs, Output, OutputConnection, Outputs};
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// Editor document, keeps everything from the Rete json so it can be written back unchanged
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "RawGraph", into = "RawGraph")]
+pub struct Graph {
+    pub id: String,
+    pub nodes: HashMap<i64, Node>,
+    pub comments: Option<Vec<Comment>>,
+    pub groups: Option<Groups>,
+    /// Fields this crate doesn't model
+    pub extra: Map<String, Value>,
+}
+
+impl Graph {
+    pub fn new(id: &str) -> Graph {
+        Graph {
+            id: id.to_string(),
+            nodes: HashMap::new(),
+            comments: Some(vec![]),
+            groups: None,
+            extra: Map::new(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Graph> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn from_value(value: Value) -> Result<Graph> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn to_value(&self) -> Result<Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    pub fn comments(&self) -> &[Comment] {
+        self.comments.as_deref().unwrap_or_default()
+    }
+
+    pub fn node_comments(&self, node_id: i64) -> Vec<&Comment> {
+        self.comments()
+            .iter()
+            .filter(|comment| comment.is_linked_to(node_id))
+            .collect()
+    }
+
+    pub fn find_comments(&self, pattern: &str) -> Vec<&Comment> {
+        self.comments()
+            .iter()
+            .filter(|comment| comment.matches(pattern))
+            .collect()
+    }
+
+    pub fn group(&self, id: i64) -> Option<&Group> {
+        self.groups.as_ref().and_then(|groups| groups.get(&id))
+    }
+
+    /// Members listed on the group together with nodes that point at it through `Node::group`
+    pub fn group_nodes(&self, id: i64) -> Vec<i64> {
+        let mut members = self
+            .group(id)
+            .map(|group| group.nodes.clone())
+            .unwrap_or_default();
+        members.extend(
+            self.nodes
+                .values()
+                .filter(|node| node.group == Some(id))
+                .map(|node| node.id),
+        );
+        members.retain(|member| self.nodes.contains_key(member));
+        members.sort_unstable();
+        members.dedup();
+        members
+    }
+
+    /// Lowest id among the nodes without connected inputs, a default start node
+    pub fn entry_node(&self) -> Option<i64> {
+        self.nodes
+            .values()
+            .filter(|node| {
+                node.inputs
+                    .iter()
+                    .flat_map(|inputs| inputs.values())
+                    .all(|input| input.connections.is_empty())
+            })
+            .map(|node| node.id)
+            .min()
+    }
+
+    /// Copy with only the nodes of the group, connections leaving the group are dropped
+    pub fn only_group(&self, id: i64) -> Graph {
+        let members = self.group_nodes(id);
+        self.retain_nodes(|node| members.contains(&node))
+    }
+
+    /// Copy without the nodes of the group, connections into the group are dropped
+    pub fn without_group(&self, id: i64) -> Graph {
+        let members = self.group_nodes(id);
+        self.retain_nodes(|node| !members.contains(&node))
+    }
+
+    fn retain_nodes<F: Fn(i64) -> bool>(&self, keep: F) -> Graph {
+        let mut graph = self.clone();
+        let removed = graph
+            .nodes
+            .keys()
+            .copied()
+            .filter(|id| !keep(*id))
+            .collect::<Vec<_>>();
+        for id in removed {
+            let _ = graph.remove_node(id);
+        }
+        graph
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawGraph {
+    id: String,
+    nodes: BTreeMap<String, Node>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comments: Option<Vec<Comment>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    groups: Option<BTreeMap<String, Group>>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl TryFrom<RawGraph> for Graph {
+    type Error = std::num::ParseIntError;
+
+    fn try_from(raw: RawGraph) -> Result<Self, Self::Error> {
+        Ok(Graph {
+            id: raw.id,
+            nodes: raw
+                .nodes
+                .into_iter()
+                .map(|(k, v)| Ok((k.parse::<i64>()?, v)))
+                .collect::<Result<HashMap<_, _>, _>>()?,
+            comments: raw.comments,
+            groups: raw
+                .groups
+                .map(|groups| {
+                    groups
+                        .into_iter()
+                        .map(|(k, v)| Ok((k.parse::<i64>()?, v)))
+                        .collect::<Result<Groups, _>>()
+                })
+                .transpose()?,
+            extra: raw.extra,
+        })
+    }
+}
+
+impl From<Graph> for RawGraph {
+    fn from(graph: Graph) -> Self {
+        RawGraph {
+            id: graph.id,
+            nodes: graph
+                .nodes
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            comments: graph.comments,
+            groups: graph.groups.map(|groups| {
+                groups
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect()
+            }),
+            extra: graph.extra,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GraphError {
+    #[error("Node not found: {0}")]
+    NodeNotFound(i64),
+    #[error("Node already exists: {0}")]
+    NodeExists(i64),
+    #[error("Node[{0}]: new nodes can't carry connections, use connect")]
+    NodeHasConnections(i64),
+    #[error("Node[{0}]: no input `{1}`")]
+    InputNotFound(i64, String),
+    #[error("Node[{0}]: no output `{1}`")]
+    OutputNotFound(i64, String),
+    #[error("Node[{0}]: socket `{1}` already exists")]
+    SocketExists(i64, String),
+    #[error("Node[{0}]: input `{1}` already has a connection")]
+    InputOccupied(i64, String),
+    #[error("Connection already exists: {0}.{1} -> {2}.{3}")]
+    ConnectionExists(i64, String, i64, String),
+    #[error("Connection not found: {0}.{1} -> {2}.{3}")]
+    ConnectionNotFound(i64, String, i64, String),
+    #[error("Connection {0}.{1} -> {2}.{3} is only stored on one side")]
+    Inconsistent(i64, String, i64, String),
+}
+
+/// Edits keep both sides of a connection in sync, the `OutputConnection` on the source
+/// and the `InputConnection` on the target
+impl Graph {
+    pub fn add_node(&mut self, mut node: Node) -> Result<(), GraphError> {
+        if self.nodes.contains_key(&node.id) {
+            return Err(GraphError::NodeExists(node.id));
+        }
+        let connected = node
+            .inputs
+            .iter()
+            .flat_map(|i| i.values())
+            .any(|i| !i.connections.is_empty())
+            || node
+                .outputs
+                .iter()
+                .flat_map(|o| o.values())
+                .any(|o| !o.connections.is_empty());
+        if connected {
+            return Err(GraphError::NodeHasConnections(node.id));
+        }
+        node.inputs.get_or_insert_with(Inputs::default);
+        node.outputs.get_or_insert_with(Outputs::default);
+        self.nodes.insert(node.id, node);
+        Ok(())
+    }
+
+    /// Removes the node, every connection to and from it and its comment links
+    pub fn remove_node(&mut self, id: i64) -> Result<Node, GraphError> {
+        let node = self.nodes.remove(&id).ok_or(GraphError::NodeNotFound(id))?;
+        for comment in self.comments.iter_mut().flatten() {
+            comment.unlink(id);
+        }
+        for other in self.nodes.values_mut() {
+            if let Some(inputs) = other.inputs.as_mut() {
+                for input in inputs.inner_mut().values_mut() {
+                    input.connections.retain(|c| c.node != id);
+                }
+            }
+            if let Some(outputs) = other.outputs.as_mut() {
+                for output in outputs.inner_mut().values_mut() {
+                    output.connections.retain(|c| c.node != id);
+                }
+            }
+        }
+        Ok(node)
+    }
+
+    pub fn set_data(&mut self, id: i64, data: Value) -> Result<(), GraphError> {
+        self.node_mut(id)?.data = Some(data);
+        Ok(())
+    }
+
+    pub fn add_input(&mut self, id: i64, name: &str) -> Result<(), GraphError> {
+        let inputs = self
+            .node_mut(id)?
+            .inputs
+            .get_or_insert_with(Inputs::default);
+        if inputs.contains_key(name) {
+            return Err(GraphError::SocketExists(id, name.to_string()));
+        }
+        inputs.inner_mut().insert(
+            name.to_string(),
+            Input {
+                connections: vec![],
+            },
+        );
+        Ok(())
+    }
+
+    pub fn add_output(&mut self, id: i64, name: &str) -> Result<(), GraphError> {
+        let outputs = self
+            .node_mut(id)?
+            .outputs
+            .get_or_insert_with(Outputs::default);
+        if outputs.contains_key(name) {
+            return Err(GraphError::SocketExists(id, name.to_string()));
+        }
+        outputs.inner_mut().insert(
+            name.to_string(),
+            Output {
+                connections: vec![],
+            },
+        );
+        Ok(())
+    }
+
+    /// Connects an existing output to an existing input. The engine reads one value per data
+    /// input, so only `action` inputs take several connections
+    pub fn connect(&mut self, from: (i64, &str), to: (i64, &str)) -> Result<(), GraphError> {
+        let (from_id, output) = from;
+        let (to_id, input) = to;
+        self.output(from_id, output)?;
+        let target = self.input(to_id, input)?;
+        if target
+            .connections
+            .iter()
+            .any(|c| c.node == from_id && c.output == output)
+        {
+            return Err(GraphError::ConnectionExists(
+                from_id,
+                output.into(),
+                to_id,
+                input.into(),
+            ));
+        }
+        if input != "action" && !target.connections.is_empty() {
+            return Err(GraphError::InputOccupied(to_id, input.into()));
+        }
+        self.output_mut(from_id, output)?
+            .connections
+            .push(OutputConnection {
+                node: to_id,
+                input: input.to_string(),
+                data: json!({}),
+            });
+        self.input_mut(to_id, input)?
+            .connections
+            .push(InputConnection {
+                node: from_id,
+                output: output.to_string(),
+                data: json!({}),
+            });
+        Ok(())
+    }
+
+    /// Both sides are checked before either is changed
+    pub fn disconnect(&mut self, from: (i64, &str), to: (i64, &str)) -> Result<(), GraphError> {
+        let (from_id, output) = from;
+        let (to_id, input) = to;
+        let not_found =
+            || GraphError::ConnectionNotFound(from_id, output.into(), to_id, input.into());
+        let source_index = self
+            .output(from_id, output)?
+            .connections
+            .iter()
+            .position(|c| c.node == to_id && c.input == input)
+            .ok_or_else(not_found)?;
+        let target_index = self
+            .input(to_id, input)?
+            .connections
+            .iter()
+            .position(|c| c.node == from_id && c.output == output)
+            .ok_or_else(not_found)?;
+        self.output_mut(from_id, output)?
+            .connections
+            .remove(source_index);
+        self.input_mut(to_id, input)?
+            .connections
+            .remove(target_index);
+        Ok(())
+    }
+
+    pub fn rename_input(&mut self, id: i64, from: &str, to: &str) -> Result<(), GraphError> {
+        let inputs = self
+            .node_mut(id)?
+            .inputs
+            .get_or_insert_with(Inputs::default);
+        if inputs.contains_key(to) {
+            return Err(GraphError::SocketExists(id, to.to_string()));
+        }
+        let input = inputs
+            .inner_mut()
+            .remove(from)
+            .ok_or(GraphError::InputNotFound(id, from.to_string()))?;
+        for connection in &input.connections {
+            if let Ok(output) = self.output_mut(connection.node, &connection.output) {
+                output
+                    .connections
+                    .iter_mut()
+                    .filter(|c| c.node == id && c.input == from)
+                    .for_each(|c| c.input = to.to_string());
+            }
+        }
+        self.node_mut(id)?
+            .inputs
+            .get_or_insert_with(Inputs::default)
+            .inner_mut()
+            .insert(to.to_string(), input);
+        Ok(())
+    }
+
+    pub fn rename_output(&mut self, id: i64, from: &str, to: &str) -> Result<(), GraphError> {
+        let outputs = self
+            .node_mut(id)?
+            .outputs
+            .get_or_insert_with(Outputs::default);
+        if outputs.contains_key(to) {
+            return Err(GraphError::SocketExists(id, to.to_string()));
+        }
+        let output = outputs
+            .inner_mut()
+            .remove(from)
+            .ok_or(GraphError::OutputNotFound(id, from.to_string()))?;
+        for connection in &output.connections {
+            if let Ok(input) = self.input_mut(connection.node, &connection.input) {
+                input
+                    .connections
+                    .iter_mut()
+                    .filter(|c| c.node == id && c.output == from)
+                    .for_each(|c| c.output = to.to_string());
+            }
+        }
+        self.node_mut(id)?
+            .outputs
+            .get_or_insert_with(Outputs::default)
+            .inner_mut()
+            .insert(to.to_string(), output);
+        Ok(())
+    }
+
+    /// Checks that every connection is stored on both nodes it links
+    pub fn validate(&self) -> Result<(), GraphError> {
+        for node in self.nodes.values() {
+            for (name, output) in node.outputs.iter().flat_map(|o| o.inner()) {
+                for c in &output.connections {
+                    let mirrored = self
+                        .input(c.node, &c.input)
+                        .map(|i| {
+                            i.connections
+                                .iter()
+                                .any(|i| i.node == node.id && &i.output == name)
+                        })
+                        .unwrap_or(false);
+                    if !mirrored {
+                        return Err(GraphError::Inconsistent(
+                            node.id,
+                            name.clone(),
+                            c.node,
+                            c.input.clone(),
+                        ));
+                    }
+                }
+            }
+            for (name, input) in node.inputs.iter().flat_map(|i| i.inner()) {
+                for c in &input.connections {
+                    let mirrored = self
+                        .output(c.node, &c.output)
+                        .map(|o| {
+                            o.connections
+                                .iter()
+                                .any(|o| o.node == node.id && &o.input == name)
+                        })
+                        .unwrap_or(false);
+                    if !mirrored {
+                        return Err(GraphError::Inconsistent(
+                            c.node,
+                            c.output.clone(),
+                            node.id,
+                            name.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn node_mut(&mut self, id: i64) -> Result<&mut Node, GraphError> {
+        self.nodes.get_mut(&id).ok_or(GraphError::NodeNotFound(id))
+    }
+
+    fn input(&self, id: i64, name: &str) -> Result<&Input, GraphError> {
+        self.nodes
+            .get(&id)
+            .ok_or(GraphError::NodeNotFound(id))?
+            .inputs
+            .as_ref()
+            .and_then(|i| i.get(name))
+            .ok_or(GraphError::InputNotFound(id, name.to_string()))
+    }
+
+    fn output(&self, id: i64, name: &str) -> Result<&Output, GraphError> {
+        self.nodes
+            .get(&id)
+            .ok_or(GraphError::NodeNotFound(id))?
+            .outputs
+            .as_ref()
+            .and_then(|o| o.get(name))
+            .ok_or(GraphError::OutputNotFound(id, name.to_string()))
+    }
+
+    fn input_mut(&mut self, id: i64, name: &str) -> Result<&mut Input, GraphError> {
+        self.node_mut(id)?
+            .inputs
+            .as_mut()
+            .and_then(|i| i.inner_mut().get_mut(name))
+            .ok_or(GraphError::InputNotFound(id, name.to_string()))
+    }
+
+    fn output_mut(&mut self, id: i64, name: &str) -> Result<&mut Output, GraphError> {
+        self.node_mut(id)?
+            .outputs
+            .as_mut()
+            .and_then(|o| o.inner_mut().get_mut(name))
+            .ok_or(GraphError::OutputNotFound(id, name.to_string()))
+    }
+}
@@ -1,14 +1,24 @@
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 
-#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct Group {
-  id: i64,
-  nodes: Vec<i64>,
-  min_width: f32,
-  max_widht: f32,
-  position: [f32; 2],
-  width: f32,
-  height: f32
+  pub id: i64,
+  #[serde(default)]
+  pub nodes: Vec<i64>,
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::node::dimension")]
+  pub min_width: Option<f64>,
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::node::dimension")]
+  pub max_width: Option<f64>,
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::node::position")]
+  pub position: Option<Vec<f64>>,
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::node::dimension")]
+  pub width: Option<f64>,
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::node::dimension")]
+  pub height: Option<f64>,
+  #[serde(flatten)]
+  pub extra: Map<String, Value>,
 }
-#[allow(dead_code)]
-pub type Groups = HashMap<i64, Group>;
\ No newline at end of file
+
+pub type Groups = HashMap<i64, Group>;
@@ -4,21 +4,76 @@
 
 mod target;
 mod group;
+mod comment;
+mod graph;
+mod format;
+#[cfg(feature = "binary")]
+mod binary;
+mod builder;
 #[macro_use] mod node;
 mod workers;
+mod conversion;
+mod context;
+mod state;
+mod version;
+mod migration;
 mod engine;
+mod export;
+mod process;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod plugin;
+#[cfg(feature = "plugins")]
+mod plugin_loader;
+#[cfg(feature = "std-workers")]
+mod expression;
+#[cfg(feature = "std-workers")]
+pub mod std_workers;
+#[cfg(feature = "script")]
+mod script;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "watch")]
+mod watch;
 
 pub use target::*;
 pub use group::*;
+pub use comment::*;
+pub use graph::*;
+pub use format::*;
+#[cfg(feature = "binary")]
+pub use binary::*;
+pub use builder::*;
 pub use node::*;
 pub use workers::*;
+pub use conversion::*;
+pub use context::*;
+pub use state::*;
+pub use version::*;
+pub use migration::*;
 pub use engine::*;
+pub use export::*;
+pub use process::*;
+#[cfg(feature = "wasm")]
+pub use wasm::*;
+pub use plugin::*;
+#[cfg(feature = "plugins")]
+pub use plugin_loader::*;
+#[cfg(feature = "std-workers")]
+pub use expression::*;
+#[cfg(feature = "script")]
+pub use script::*;
+#[cfg(feature = "server")]
+pub use server::*;
+#[cfg(feature = "watch")]
+pub use watch::*;
 
 #[cfg(test)]
 mod tests {
-  use crate::{node::*, WorkerError, Worker};
-  use crate::engine::Engine;
+  use crate::{node::*, Cancellation, Compatibility, Context, Conversions, JsonFileStateStore, LogLevel, RunContext, StateStore, WorkerError, Worker};
+  use crate::engine::{Engine, EngineError};
   use crate::workers::WorkersBuilder;
+  use crate::{to_dot, to_mermaid, AppliedMigration, Comment, ExecutionOverlay, Graph, GraphBuilder, GraphFormat, GraphError, Migration};
   use anyhow::Result;
 
   #[test]
@@ -179,6 +234,148 @@ mod tests {
     assert_eq!(result, &8i64);
   }
 
+  const MULTIPLY_JSON: &str = r#"
+    {
+      "id": "demo@0.1.1",
+      "nodes": {
+        "1": {
+          "id": 1,
+          "data": {
+            "num": 2
+          },
+          "inputs": {},
+          "outputs": {
+            "num": {
+              "connections": [{
+                "node": 3,
+                "input": "num",
+                "data": {}
+              }, {
+                "node": 4,
+                "input": "num2",
+                "data": {}
+              }, {
+                "node": 5,
+                "input": "num2",
+                "data": {}
+              }]
+            }
+          },
+          "position": [-60, 182],
+          "name": "Number"
+        },
+        "2": {
+          "id": 2,
+          "data": {
+            "num": 0
+          },
+          "inputs": {},
+          "outputs": {
+            "num": {
+              "connections": [{
+                "node": 3,
+                "input": "num2",
+                "data": {}
+              }]
+            }
+          },
+          "position": [-106, 378],
+          "name": "Number"
+        },
+        "3": {
+          "id": 3,
+          "data": {},
+          "inputs": {
+            "num": {
+              "connections": [{
+                "node": 1,
+                "output": "num",
+                "data": {}
+              }]
+            },
+            "num2": {
+              "connections": [{
+                "node": 2,
+                "output": "num",
+                "data": {}
+              }]
+            }
+          },
+          "outputs": {
+            "num": {
+              "connections": [{
+                "node": 4,
+                "input": "num",
+                "data": {}
+              }]
+            }
+          },
+          "position": [241, 240],
+          "name": "Add"
+        },
+        "4": {
+          "id": 4,
+          "data": {},
+          "inputs": {
+            "num": {
+              "connections": [{
+                "node": 3,
+                "output": "num",
+                "data": {}
+              }]
+            },
+            "num2": {
+              "connections": [{
+                "node": 1,
+                "output": "num",
+                "data": {}
+              }]
+            }
+          },
+          "outputs": {
+            "num": {
+              "connections": [{
+                "node": 5,
+                "input": "num",
+                "data": {}
+              }]
+            }
+          },
+          "position": [552.5, 204],
+          "name": "Add"
+        },
+        "5": {
+          "id": 5,
+          "data": {},
+          "inputs": {
+            "num": {
+              "connections": [{
+                "node": 4,
+                "output": "num",
+                "data": {}
+              }]
+            },
+            "num2": {
+              "connections": [{
+                "node": 1,
+                "output": "num",
+                "data": {}
+              }]
+            }
+          },
+          "outputs": {
+            "num": {
+              "connections": []
+            }
+          },
+          "position": [826.5, 292],
+          "name": "Multiply"
+        }
+      },
+      "comments": []
+    }
+    "#;
+
   #[test]
   fn add_works() {
     let json_data = r#"
@@ -500,6 +697,1353 @@ mod tests {
     assert_eq!(err.to_string(), expected.to_string());
   }
 
+  #[test]
+  fn process_all_collects_errors() {
+    let json_data = r#"
+    {
+      "id": "demo@0.1.0",
+      "nodes": {
+        "1": {
+          "id": 1,
+          "data": { "num": 2 },
+          "inputs": {},
+          "outputs": {
+            "num": {
+              "connections": [
+                { "node": 2, "input": "num", "data": {} },
+                { "node": 4, "input": "num", "data": {} }
+              ]
+            }
+          },
+          "position": [0, 0],
+          "name": "Number"
+        },
+        "2": {
+          "id": 2,
+          "data": {},
+          "inputs": {
+            "num": { "connections": [{ "node": 1, "output": "num", "data": {} }] },
+            "num2": { "connections": [{ "node": 3, "output": "num", "data": {} }] }
+          },
+          "outputs": {
+            "num": { "connections": [{ "node": 5, "input": "num", "data": {} }] }
+          },
+          "position": [200, 0],
+          "name": "Add"
+        },
+        "3": {
+          "id": 3,
+          "data": { "num": "abc" },
+          "inputs": {},
+          "outputs": {
+            "num": { "connections": [{ "node": 2, "input": "num2", "data": {} }] }
+          },
+          "position": [0, 200],
+          "name": "Number"
+        },
+        "4": {
+          "id": 4,
+          "data": { "num2": 5 },
+          "inputs": {
+            "num": { "connections": [{ "node": 1, "output": "num", "data": {} }] }
+          },
+          "outputs": {
+            "num": { "connections": [] }
+          },
+          "position": [200, 200],
+          "name": "Add"
+        },
+        "5": {
+          "id": 5,
+          "data": { "num2": 1 },
+          "inputs": {
+            "num": { "connections": [{ "node": 2, "output": "num", "data": {} }] }
+          },
+          "outputs": {
+            "num": { "connections": [] }
+          },
+          "position": [400, 0],
+          "name": "Add"
+        }
+      },
+      "comments": []
+    }
+    "#;
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number)
+      .add(Add);
+
+    let engine = Engine::new("demo@0.1.0", workers.build());
+    let nodes = engine.parse_json(json_data).unwrap();
+    let report = engine.process_all(&nodes, 1);
+    assert!(!report.is_ok());
+    assert_eq!(report.errors.keys().collect::<Vec<_>>(), vec![&3]);
+    assert_eq!(report.skipped.iter().collect::<Vec<_>>(), vec![&2, &5]);
+    assert_eq!(report.outputs[&4]["num"].get::<i64>(), Some(&7i64));
+    assert!(engine.process(&nodes, 1).is_err());
+    let err = report.into_result().err().unwrap();
+    assert!(err.to_string().starts_with("1 node(s) failed"));
+  }
+
+  #[test]
+  fn output_values_are_inspectable() {
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    struct Point { x: i64, y: i64 }
+
+    let output = OutputDataBuilder::new()
+      .data("point", Box::new(Point { x: 1, y: 2 }))
+      .data("num", Box::new(3i64))
+      .raw("raw", Box::new(std::time::Duration::from_secs(1)))
+      .build();
+
+    assert_eq!(format!("{:?}", output["point"]), "NodeResult(Point { x: 1, y: 2 })");
+    assert_eq!(output["point"].get::<Point>(), Some(&Point { x: 1, y: 2 }));
+    assert_eq!(output["point"].try_clone().unwrap(), output["point"].0);
+    assert_ne!(output["point"], output["num"]);
+    assert_eq!(output["point"].to_json().unwrap(), json!({"x": 1, "y": 2}));
+    assert!(output["raw"].try_clone().is_none());
+    assert!(output.to_json().is_err());
+  }
+
+  #[test]
+  fn conversions_between_sockets() {
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    struct Decimal(i64, u32);
+
+    let node: Node = serde_json::from_value(json!({
+      "id": 2,
+      "name": "Add",
+      "data": {},
+      "inputs": {
+        "num": { "connections": [{ "node": 1, "output": "num", "data": {} }] },
+        "dec": { "connections": [{ "node": 1, "output": "dec", "data": {} }] }
+      }
+    })).unwrap();
+    let upstream = OutputDataBuilder::new()
+      .data("num", Box::new(2i32))
+      .data("dec", Box::new(Decimal(125, 2)))
+      .build();
+
+    let mut conversions = Conversions::default();
+    conversions.register(|d: &Decimal| Ok(format!("{}.{}", d.0 / 10i64.pow(d.1), d.0 % 10i64.pow(d.1))));
+    let inputs = InputDataBuilder::new()
+      .add_data("num".into(), upstream.0.clone().into())
+      .add_data("dec".into(), upstream.0.clone().into())
+      .conversions(std::rc::Rc::new(conversions))
+      .build();
+
+    assert_eq!(node.get_number_field("num", &inputs).unwrap(), 2);
+    assert_eq!(node.get_float_number_field("num", &inputs).unwrap(), 2.0);
+    assert_eq!(node.get_string_field("dec", &inputs).unwrap(), "1.25");
+    assert_eq!(node.get_as_json_field("dec", &inputs).unwrap(), json!([125, 2]));
+    assert!(node.get_number_field("dec", &inputs).is_err());
+  }
+
+  #[test]
+  fn socket_checks_use_the_conversion_registry() {
+    let node: Node = serde_json::from_value(json!({
+      "id": 2,
+      "name": "Add",
+      "data": {},
+      "inputs": {
+        "num": { "connections": [{ "node": 1, "output": "num", "data": {} }] },
+        "raw": { "connections": [{ "node": 1, "output": "raw", "data": {} }] }
+      }
+    })).unwrap();
+    let upstream = OutputDataBuilder::new()
+      .data("num", Box::new(2i32))
+      .raw("raw", Box::new(std::time::Duration::from_secs(1)))
+      .build();
+    let inputs = InputDataBuilder::new()
+      .add_data("num".into(), upstream.0.clone().into())
+      .add_data("raw".into(), upstream.0.clone().into())
+      .build();
+
+    assert!(node.check_input::<i64>("num", &inputs).is_ok());
+    assert!(node.check_input::<f64>("num", &inputs).is_ok());
+    assert!(node.check_input::<serde_json::Value>("num", &inputs).is_ok());
+    assert!(node.check_input::<String>("num", &inputs).is_err());
+    assert!(node.check_input::<serde_json::Value>("raw", &inputs).is_err());
+    assert!(node.check_input::<String>("missing", &inputs).is_ok());
+
+    assert!(node.get_as_json_field_or("raw", &inputs, Some(json!("fallback"))).is_err());
+    assert_eq!(node.get_as_json_field_or("missing", &inputs, Some(json!("fallback"))).unwrap(), json!("fallback"));
+    assert!(node.get_as_json_field("raw", &inputs).is_err());
+  }
+
+  #[test]
+  fn workers_receive_context() {
+    struct Scale(i64);
+    struct Scaled;
+    impl Worker for Scaled {
+      fn name(&self) -> &str {
+        "Scaled"
+      }
+
+      fn work(&self, _node: &Node, _input_data: InputData) -> Result<OutputData> {
+        bail!("needs a run context")
+      }
+
+      fn work_with_context(&self, node: &Node, input_data: InputData, ctx: &Context) -> Result<OutputData> {
+        let num = node.get_number_field("num", &input_data)?;
+        let scale = ctx.extension::<Scale>().map(|s| s.0).unwrap_or(1);
+        let offset = ctx.param("offset").and_then(|o| o.as_i64()).unwrap_or(0);
+        ctx.log(LogLevel::Info, format_args!("scaling {} by {}", num, scale));
+        Ok(OutputDataBuilder::new()
+          .data("num", Box::new(num * scale + offset))
+          .data("run", Box::new(ctx.run_id()))
+          .build())
+      }
+    }
+
+    let json_data = r#"
+    {
+      "id": "demo@0.1.0",
+      "nodes": {
+        "1": { "id": 1, "data": { "num": 3 }, "inputs": {}, "outputs": {}, "position": [0, 0], "name": "Scaled" }
+      },
+      "comments": []
+    }
+    "#;
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Scaled);
+    let mut engine = Engine::new("demo@0.1.0", workers.build());
+    engine.extensions_mut().insert(Scale(2));
+    let logs = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    let sink = logs.clone();
+    engine.set_logger(move |record| sink.borrow_mut().push((record.node_id, record.message.to_string())));
+    let nodes = engine.parse_json(json_data).unwrap();
+
+    let context = RunContext::new().params(json!({ "offset": 1 }));
+    let run_id = context.run_id;
+    let output = engine.process_with(&nodes, 1, context).unwrap();
+    assert_eq!(output["num"].get::<i64>(), Some(&7));
+    assert_eq!(output["run"].get::<u64>(), Some(&run_id));
+    assert_eq!(logs.borrow().as_slice(), &[(1, "scaling 3 by 2".to_owned())]);
+
+    let cancellation = Cancellation::new();
+    cancellation.cancel();
+    let err = engine.process_with(&nodes, 1, RunContext::new().cancellation(cancellation)).err().unwrap();
+    assert!(err.to_string().ends_with("was cancelled"));
+  }
+
+  #[test]
+  fn workers_keep_state_between_runs() {
+    struct Counter;
+    impl Worker for Counter {
+      fn name(&self) -> &str {
+        "Counter"
+      }
+
+      fn work(&self, _node: &Node, _input_data: InputData) -> Result<OutputData> {
+        bail!("needs a run context")
+      }
+
+      fn work_with_context(&self, _node: &Node, _input_data: InputData, ctx: &Context) -> Result<OutputData> {
+        let count = ctx.state::<i64>()?.unwrap_or(0) + 1;
+        ctx.set_state(&count)?;
+        Ok(OutputDataBuilder::new()
+          .data("count", Box::new(count))
+          .build())
+      }
+    }
+
+    let json_data = r#"
+    {
+      "id": "demo@0.1.0",
+      "nodes": {
+        "1": { "id": 1, "data": {}, "inputs": {}, "outputs": {}, "position": [0, 0], "name": "Counter" }
+      },
+      "comments": []
+    }
+    "#;
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Counter);
+    let engine = Engine::new("demo@0.1.0", workers.build());
+    let nodes = engine.parse_json(json_data).unwrap();
+    assert_eq!(engine.process(&nodes, 1).unwrap()["count"].get::<i64>(), Some(&1));
+    assert_eq!(engine.process(&nodes, 1).unwrap()["count"].get::<i64>(), Some(&2));
+    let snapshot = engine.snapshot("", &nodes).unwrap();
+    engine.reset_node_state("", 1).unwrap();
+    assert_eq!(engine.process(&nodes, 1).unwrap()["count"].get::<i64>(), Some(&1));
+
+    let path = std::env::temp_dir().join(format!("d3ne-state-{}.json", std::process::id()));
+    let mut workers = WorkersBuilder::new();
+    workers.add(Counter);
+    let mut engine = Engine::new("demo@0.1.0", workers.build());
+    engine.set_state_store(JsonFileStateStore::open(&path).unwrap());
+    let run = |graph_id: &str| engine.process_with(&nodes, 1, RunContext::new().graph_id(graph_id)).unwrap()["count"].get::<i64>().copied();
+    assert_eq!(run("a"), Some(1));
+    assert_eq!(run("a"), Some(2));
+    assert_eq!(run("b"), Some(1));
+    assert_eq!(engine.state().get("a", 1).unwrap(), Some(json!(2)));
+
+    let nodes = engine.restore(snapshot).unwrap();
+    assert_eq!(engine.process(&nodes, 1).unwrap()["count"].get::<i64>(), Some(&3));
+    assert_eq!(JsonFileStateStore::open(&path).unwrap().get("", 1).unwrap(), Some(json!(3)));
+    assert_eq!(engine.state().get("a", 1).unwrap(), Some(json!(2)));
+    let a = engine.snapshot("a", &nodes).unwrap();
+    assert_eq!(a.state, [(1, json!(2))].into_iter().collect());
+
+    engine.reset_graph_state("a").unwrap();
+    assert_eq!(engine.state().get("a", 1).unwrap(), None);
+    assert_eq!(engine.state().get("b", 1).unwrap(), Some(json!(1)));
+    engine.restore(a).unwrap();
+    assert_eq!(run("a"), Some(3));
+    engine.reset_state().unwrap();
+    let reopened = JsonFileStateStore::open(&path).unwrap();
+    assert_eq!((reopened.get("", 1).unwrap(), reopened.get("b", 1).unwrap()), (None, None));
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn worker_lifecycle_hooks() {
+    use std::{cell::RefCell, rc::Rc};
+
+    struct Tracked(Rc<RefCell<Vec<String>>>);
+    impl Worker for Tracked {
+      fn name(&self) -> &str {
+        "Tracked"
+      }
+
+      fn init(&self) -> Result<()> {
+        self.0.borrow_mut().push("init".into());
+        Ok(())
+      }
+
+      fn before_run(&self, _context: &RunContext) -> Result<()> {
+        self.0.borrow_mut().push("before_run".into());
+        Ok(())
+      }
+
+      fn work(&self, _node: &Node, _input_data: InputData) -> Result<OutputData> {
+        self.0.borrow_mut().push("work".into());
+        Ok(OutputDataBuilder::new().build())
+      }
+
+      fn after_run(&self, _context: &RunContext) -> Result<()> {
+        self.0.borrow_mut().push("after_run".into());
+        Ok(())
+      }
+
+      fn shutdown(&self) {
+        self.0.borrow_mut().push("shutdown".into());
+      }
+    }
+
+    struct Broken;
+    impl Worker for Broken {
+      fn name(&self) -> &str {
+        "Broken"
+      }
+
+      fn init(&self) -> Result<()> {
+        bail!("no connection")
+      }
+
+      fn work(&self, _node: &Node, _input_data: InputData) -> Result<OutputData> {
+        Ok(OutputDataBuilder::new().build())
+      }
+    }
+
+    let json_data = r#"
+    {
+      "id": "demo@0.1.0",
+      "nodes": {
+        "1": { "id": 1, "data": {}, "inputs": {}, "outputs": {}, "position": [0, 0], "name": "Tracked" }
+      },
+      "comments": []
+    }
+    "#;
+
+    let events = Rc::new(RefCell::new(vec![]));
+    let mut workers = WorkersBuilder::new();
+    workers.add(Tracked(events.clone()));
+    let engine = Engine::new("demo@0.1.0", workers.build());
+    let nodes = engine.parse_json(json_data).unwrap();
+    engine.process(&nodes, 1).unwrap();
+    drop(engine);
+    assert_eq!(events.borrow().as_slice(), &["init", "before_run", "work", "after_run", "shutdown"]);
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Broken);
+    let err = workers.try_build().err().unwrap();
+    assert_eq!(err.to_string(), "Worker `Broken` init failed: no connection");
+
+    let events = Rc::new(RefCell::new(vec![]));
+    let mut workers = WorkersBuilder::new();
+    workers.add(Tracked(events.clone())).add(Broken);
+    assert!(workers.try_build().is_err());
+    let events = events.borrow();
+    assert_eq!(events.contains(&"init".to_string()), events.contains(&"shutdown".to_string()));
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Broken);
+    let engine = Engine::new("demo@0.1.0", workers.build());
+    let err = engine.process(&engine.parse_json(&json_data.replace("Tracked", "Broken")).unwrap(), 1).err().unwrap();
+    assert!(err.to_string().contains("init failed: no connection"));
+
+    struct Refusing;
+    impl Worker for Refusing {
+      fn name(&self) -> &str {
+        "Refusing"
+      }
+
+      fn before_run(&self, _context: &RunContext) -> Result<()> {
+        bail!("not now")
+      }
+
+      fn work(&self, _node: &Node, _input_data: InputData) -> Result<OutputData> {
+        Ok(OutputDataBuilder::new().build())
+      }
+    }
+
+    // worker order is random, every started worker has to be torn down whichever fails first
+    let events = Rc::new(RefCell::new(vec![]));
+    let count = |event: &str| events.borrow().iter().filter(|e| *e == event).count();
+    for _ in 0..8 {
+      let mut workers = WorkersBuilder::new();
+      workers.add(Tracked(events.clone())).add(Refusing);
+      let engine = Engine::new("demo@0.1.0", workers.build());
+      let nodes = engine.parse_json(json_data).unwrap();
+      assert_eq!(engine.process(&nodes, 1).unwrap_err().to_string(), "not now");
+      assert_eq!(engine.process_all(&nodes, 1).errors[&1].to_string(), "not now");
+      assert_eq!(count("before_run"), count("after_run"));
+    }
+    assert_eq!(count("work"), 0);
+  }
+
+  #[test]
+  fn graph_round_trips_editor_json() {
+    let json_data = r#"
+    {
+      "id": "demo@0.1.0",
+      "nodes": {
+        "1": {
+          "id": 1,
+          "data": { "num": 2 },
+          "inputs": {},
+          "outputs": {
+            "num": { "connections": [{ "node": 2, "input": "num", "data": { "pin": [1, 2] } }] }
+          },
+          "position": [-60, 182.33333333333334],
+          "name": "Number",
+          "extraField": "x"
+        },
+        "2": {
+          "id": 2,
+          "data": { "num2": 5 },
+          "group": 1,
+          "inputs": {
+            "num": { "connections": [{ "node": 1, "output": "num", "data": {} }] }
+          },
+          "outputs": {
+            "num": { "connections": [] }
+          },
+          "position": [552.5, 204],
+          "name": "Add"
+        },
+        "3": { "id": 3, "data": null, "position": [10.1, 0], "name": "Number" }
+      },
+      "comments": [
+        { "type": "inline", "id": "c1", "text": "TODO", "position": [10, 20], "links": [2] }
+      ],
+      "groups": {
+        "1": { "id": 1, "nodes": [2], "position": [500, 150], "width": 300, "height": 200 }
+      },
+      "meta": { "author": "lemonxah" }
+    }
+    "#;
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add);
+    let engine = Engine::new("demo@0.1.0", workers.build());
+    let graph = engine.parse_graph_json(json_data).unwrap();
+    assert_eq!(graph.id, "demo@0.1.0");
+    assert_eq!(graph.extra["meta"], json!({ "author": "lemonxah" }));
+    assert_eq!(graph.nodes[&1].position, Some(vec![-60.0, 182.33333333333334]));
+    assert_eq!(graph.nodes[&1].extra["extraField"], json!("x"));
+    assert_eq!(graph.nodes[&3].data, Some(serde_json::Value::Null));
+    assert_eq!(engine.process(&graph.nodes, 1).unwrap()["num"].get::<i64>(), Some(&7));
+
+    let original: serde_json::Value = serde_json::from_str(json_data).unwrap();
+    let saved: serde_json::Value = serde_json::from_str(&graph.to_json().unwrap()).unwrap();
+    assert_eq!(saved, original);
+  }
+
+  #[test]
+  fn builder_matches_parsed_json() {
+    let nodes = GraphBuilder::new("demo@0.1.1")
+      .node(1, "Number", json!({ "num": 2 }))
+      .node(2, "Number", json!({ "num": 0 }))
+      .node(3, "Add", json!({}))
+      .node(4, "Add", json!({}))
+      .node(5, "Multiply", json!({}))
+      .connect((1, "num"), (3, "num"))
+      .connect((1, "num"), (4, "num2"))
+      .connect((1, "num"), (5, "num2"))
+      .connect((2, "num"), (3, "num2"))
+      .connect((3, "num"), (4, "num"))
+      .connect((4, "num"), (5, "num"))
+      .output(5, "num")
+      .unwrap()
+      .build()
+      .unwrap();
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add).add(Multiply);
+    let engine = Engine::new("demo@0.1.1", workers.build());
+    let mut parsed = engine.parse_json(MULTIPLY_JSON).unwrap();
+    parsed.values_mut().for_each(|node| node.position = None);
+    assert_eq!(nodes, parsed);
+    assert_eq!(engine.process(&nodes, 1).unwrap()["num"].get::<i64>(), Some(&8));
+
+    let mut builder = GraphBuilder::new("demo@0.1.1");
+    let a = builder.add_node("Number", json!({ "num": 1 }));
+    let b = builder.add_node("Add", json!({ "num2": 1 }));
+    builder.add_connection((a, "num"), (b, "num"));
+    assert_eq!((a, b), (1, 2));
+    assert!(builder.connect((b, "num"), (9, "num")).build().is_err());
+    assert!(matches!(GraphBuilder::new("demo@0.1.1").input(9, "num"), Err(GraphError::NodeNotFound(9))));
+    let declared = GraphBuilder::new("demo@0.1.1").node(1, "Number", json!({})).output(1, "num").unwrap();
+    assert!(matches!(declared.output(1, "num"), Err(GraphError::SocketExists(1, _))));
+  }
+
+  #[test]
+  fn graph_edits_keep_connections_consistent() {
+    let mut graph = GraphBuilder::new("demo@0.1.0")
+      .node(1, "Number", json!({ "num": 2 }))
+      .node(2, "Number", json!({ "num": 3 }))
+      .node(3, "Add", json!({}))
+      .connect((1, "num"), (3, "num"))
+      .connect((2, "num"), (3, "num2"))
+      .output(3, "num")
+      .unwrap()
+      .build_graph()
+      .unwrap();
+
+    assert!(matches!(graph.connect((2, "num"), (3, "num")), Err(GraphError::InputOccupied(3, _))));
+    assert!(matches!(graph.connect((2, "num"), (3, "missing")), Err(GraphError::InputNotFound(3, _))));
+    assert!(matches!(graph.connect((2, "nope"), (3, "num")), Err(GraphError::OutputNotFound(2, _))));
+
+    graph.rename_input(3, "num2", "other").unwrap();
+    assert_eq!(graph.nodes[&2].outputs.as_ref().unwrap()["num"].connections[0].input, "other");
+    graph.rename_output(1, "num", "value").unwrap();
+    assert_eq!(graph.nodes[&3].inputs.as_ref().unwrap()["num"].connections[0].output, "value");
+    graph.validate().unwrap();
+
+    graph.disconnect((1, "value"), (3, "num")).unwrap();
+    assert!(graph.nodes[&1].outputs.as_ref().unwrap()["value"].connections.is_empty());
+    assert!(graph.nodes[&3].inputs.as_ref().unwrap()["num"].connections.is_empty());
+
+    graph.remove_node(2).unwrap();
+    assert!(graph.nodes[&3].inputs.as_ref().unwrap()["other"].connections.is_empty());
+    graph.set_data(1, json!({ "num": 4 })).unwrap();
+    assert_eq!(graph.nodes[&1].data, Some(json!({ "num": 4 })));
+    assert!(matches!(graph.set_data(2, json!({})), Err(GraphError::NodeNotFound(2))));
+    graph.validate().unwrap();
+
+    let mut broken = graph.clone();
+    broken.nodes.get_mut(&1).unwrap().outputs.as_mut().unwrap().inner_mut().get_mut("value").unwrap()
+      .connections.push(crate::OutputConnection { node: 3, input: "num".into(), data: json!({}) });
+    assert!(matches!(broken.validate(), Err(GraphError::Inconsistent(1, _, 3, _))));
+  }
+
+  #[test]
+  fn engine_version_compatibility() {
+    let graph = |id: &str| json!({ "id": id, "nodes": {}, "comments": [] });
+
+    let mut engine = Engine::new("demo@1.4.2", WorkersBuilder::new().build());
+    let err = engine.parse_value(graph("demo@1.3.0")).err().unwrap();
+    assert_eq!(err.to_string(), "Version mismatch: Engine(demo@1.4.2), Nodes(demo@1.3.0): exact match required");
+
+    engine.set_compatibility(Compatibility::SameMajor);
+    assert!(engine.parse_value(graph("demo@1.3.0")).is_ok());
+    assert!(engine.parse_value(graph("demo@1.4.2")).is_ok());
+    let err = engine.parse_value(graph("demo@1.5.0")).err().unwrap();
+    assert!(err.to_string().ends_with("graph version 1.5.0 is newer than engine version 1.4.2"));
+    let err = engine.parse_value(graph("demo@0.9.0")).err().unwrap();
+    assert!(err.to_string().ends_with("major version 0 differs from 1"));
+    let err = engine.parse_value(graph("other@1.0.0")).err().unwrap();
+    assert!(err.to_string().ends_with("name `other` differs from `demo`"));
+
+    engine.set_compatibility(Compatibility::Range(">=1.2, <1.5".parse().unwrap()));
+    assert!(engine.parse_value(graph("demo@1.2.0")).is_ok());
+    let err = engine.parse_value(graph("demo@1.1.9")).err().unwrap();
+    assert!(err.to_string().ends_with("version 1.1.9 does not match `>=1.2, <1.5`"));
+    assert!(engine.parse_value(graph("demo")).is_err());
+  }
+
+  #[test]
+  fn migrations_upgrade_old_graphs() {
+    let rename_worker = || Migration::nodes("demo@0.1.0", "demo@0.2.0", |nodes| {
+      nodes.values_mut().filter(|n| n.name == "Sum").for_each(|n| n.name = "Add".into());
+      Ok(())
+    });
+    let rename_field = || Migration::json("demo@0.2.0", "demo@0.3.0", |graph| {
+      let data = &mut graph["nodes"]["1"]["data"];
+      data["num"] = data["value"].take();
+      Ok(())
+    });
+
+    let old = GraphBuilder::new("demo@0.1.0")
+      .node(1, "Number", json!({ "value": 2 }))
+      .node(2, "Sum", json!({ "num2": 3 }))
+      .connect((1, "num"), (2, "num"))
+      .output(2, "num")
+      .unwrap()
+      .build_graph()
+      .unwrap()
+      .to_value()
+      .unwrap();
+
+    let migrated = rename_worker().apply(old.clone()).unwrap();
+    assert_eq!(migrated["id"], "demo@0.2.0");
+    assert_eq!(migrated["nodes"]["2"]["name"], "Add");
+    assert!(rename_field().apply(old.clone()).is_err());
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add);
+    let mut engine = Engine::new("demo@0.3.0", workers.build());
+    engine.add_migration(rename_worker()).add_migration(rename_field());
+    let (graph, applied) = engine.parse_graph_migrated(old).unwrap();
+    assert_eq!(applied, vec![
+      AppliedMigration { from: "demo@0.1.0".into(), to: "demo@0.2.0".into() },
+      AppliedMigration { from: "demo@0.2.0".into(), to: "demo@0.3.0".into() },
+    ]);
+    assert_eq!(engine.process(&graph.nodes, 1).unwrap()["num"].get::<i64>(), Some(&5));
+  }
+
+  #[test]
+  fn groups_select_nodes_to_run() {
+    let mut graph = GraphBuilder::new("demo@0.1.0")
+      .node(1, "Number", json!({ "num": 2 }))
+      .node(2, "Add", json!({ "num2": 5 }))
+      .node(3, "Add", json!({ "num2": 1 }))
+      .node(4, "Number", json!({ "num": 10 }))
+      .connect((1, "num"), (3, "num"))
+      .connect((4, "num"), (2, "num"))
+      .connect((2, "num"), (3, "num2"))
+      .output(3, "num")
+      .unwrap()
+      .build_graph()
+      .unwrap()
+      .to_value()
+      .unwrap();
+    graph["groups"] = json!({
+      "1": { "id": 1, "nodes": [2], "minWidth": 300, "position": [100, 50.5], "width": 400, "height": 200, "title": "optional" }
+    });
+    graph["nodes"]["4"]["group"] = json!(1);
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add);
+    let engine = Engine::new("demo@0.1.0", workers.build());
+    let graph = engine.parse_graph(graph).unwrap();
+    let group = graph.group(1).unwrap();
+    assert_eq!((group.min_width, group.width, group.extra["title"].as_str()), (Some(300.0), Some(400.0), Some("optional")));
+    assert_eq!(graph.group_nodes(1), vec![2, 4]);
+    assert_eq!(graph.to_value().unwrap()["groups"]["1"]["minWidth"], json!(300));
+
+    assert_eq!(engine.process(&graph.nodes, 1).unwrap()["num"].get::<i64>(), Some(&17));
+    assert_eq!(engine.process_group(&graph, 1, 4).unwrap()["num"].get::<i64>(), Some(&15));
+    assert_eq!(engine.process_group_with(&graph, 1, 4, RunContext::new().graph_id("flow")).unwrap()["num"].get::<i64>(), Some(&15));
+    let without = graph.without_group(1);
+    assert!(without.nodes[&3].inputs.as_ref().unwrap()["num2"].connections.is_empty());
+    assert_eq!(engine.process_without_group(&graph, 1, 1).unwrap()["num"].get::<i64>(), Some(&3));
+
+    let error = engine.process_group(&graph, 1, 1).unwrap_err();
+    assert!(matches!(error.downcast_ref::<EngineError>(), Some(EngineError::StartNodeNotFound(1))));
+    assert!(engine.process_without_group(&graph, 1, 4).is_err());
+    let report = engine.process_all(&graph.nodes, 99);
+    assert!(report.errors[&99].to_string().contains("Start node 99"));
+  }
+
+  #[test]
+  fn comments_are_parsed_and_searchable() {
+    let mut graph = GraphBuilder::new("demo@0.1.0")
+      .node(1, "Number", json!({ "num": 2 }))
+      .node(2, "Add", json!({ "num2": 5 }))
+      .connect((1, "num"), (2, "num"))
+      .build_graph()
+      .unwrap()
+      .to_value()
+      .unwrap();
+    graph["comments"] = json!([
+      { "type": "frame", "id": "f1", "text": "Inputs", "position": [0, 0], "links": [1], "width": 320, "height": 180.5 },
+      { "type": "inline", "id": "i1", "text": "TODO: clamp the result", "position": [200, 10], "links": [2], "color": "red" },
+      { "type": "sticky", "text": "From a newer editor", "links": [2] },
+      { "text": "No type", "links": [1] }
+    ]);
+
+    let parsed = Graph::from_value(graph.clone()).unwrap();
+    assert!(matches!(&parsed.comments()[0], Comment::Frame(frame) if frame.height == Some(180.5)));
+    let todos = parsed.find_comments("todo");
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].links(), &[2]);
+    assert_eq!(parsed.node_comments(1)[0].text(), "Inputs");
+    assert_eq!(parsed.to_value().unwrap(), graph);
+
+    let mut edited = parsed.clone();
+    edited.remove_node(2).unwrap();
+    assert!(edited.node_comments(2).is_empty());
+    assert!(edited.comments()[1].links().is_empty());
+    assert!(matches!(&parsed.comments()[2], Comment::Other(value) if value["type"] == "sticky"));
+    assert_eq!(parsed.node_comments(2).len(), 2);
+    assert_eq!(edited.comments()[2], Comment::Other(json!({ "type": "sticky", "text": "From a newer editor", "links": [] })));
+    assert_eq!(edited.comments()[3].text(), "No type");
+  }
+
+  #[test]
+  fn export_dot_and_mermaid() {
+    let nodes = GraphBuilder::new("demo@0.1.0")
+      .node(1, "Number", json!({ "num": 2 }))
+      .node(2, "Number", json!({ "num": "abc" }))
+      .node(3, "Add", json!({}))
+      .node(4, "Add", json!({ "num": 1, "num2": 1 }))
+      .connect((1, "num"), (3, "num"))
+      .connect((2, "num"), (3, "num2"))
+      .connect((3, "action"), (4, "action"))
+      .build()
+      .unwrap();
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add);
+    let engine = Engine::new("demo@0.1.0", workers.build());
+    let overlay = ExecutionOverlay::from(&engine.process_all(&nodes, 1));
+
+    assert_eq!(to_dot(&nodes, None), r#"digraph {
+  rankdir=LR;
+  node [shape=box];
+  n1 [label="1: Number"];
+  n2 [label="2: Number"];
+  n3 [label="3: Add"];
+  n4 [label="4: Add"];
+  n1 -> n3 [label="num → num"];
+  n2 -> n3 [label="num → num2"];
+  n3 -> n4 [label="action → action", style=dashed, color=blue];
+}
+"#);
+    assert_eq!(to_mermaid(&nodes, Some(&overlay)), r#"flowchart LR
+  n1["1: Number"]
+  n2["2: Number"]
+  n3["3: Add"]
+  n4["4: Add"]
+  n1 -- "num → num" --> n3
+  n2 -- "num → num2" --> n3
+  n3 -. "action → action" .-> n4
+  classDef executed fill:#c8e6c9
+  class n1 executed
+  classDef failed fill:#ffcdd2
+  class n2 failed
+  classDef skipped fill:#ffe0b2
+  class n3,n4 skipped
+"#);
+    assert!(to_dot(&nodes, Some(&overlay)).contains(r##"n2 [label="2: Number", style=filled, fillcolor="#ffcdd2", tooltip="failed"];"##));
+  }
+
+  #[test]
+  fn graph_formats_round_trip() {
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add).add(Multiply);
+    let engine = Engine::new("demo@0.1.1", workers.build());
+    let graph = engine.parse_graph_json(MULTIPLY_JSON).unwrap();
+
+    let pretty = graph.to_format(GraphFormat::Json).unwrap();
+    assert_eq!(pretty, graph.to_format(GraphFormat::Json).unwrap());
+    assert!(pretty.starts_with("{\n  \"comments\": [],\n  \"id\": \"demo@0.1.1\",\n  \"nodes\": {\n    \"1\": {"));
+    assert_eq!(GraphFormat::detect(&pretty), GraphFormat::Json);
+    assert_eq!(GraphFormat::from_path("flows/demo.yml"), Some(GraphFormat::Yaml));
+
+    let path = std::env::temp_dir().join(format!("d3ne-graph-{}.json", std::process::id()));
+    graph.save(&path).unwrap();
+    assert_eq!(engine.load_graph(&path).unwrap(), graph);
+    std::fs::remove_file(path).unwrap();
+
+    #[cfg(feature = "yaml")]
+    assert_round_trip(&graph, GraphFormat::Yaml);
+    #[cfg(feature = "ron")]
+    assert_round_trip(&graph, GraphFormat::Ron);
+  }
+
+  #[cfg(any(feature = "yaml", feature = "ron"))]
+  fn assert_round_trip(graph: &Graph, format: GraphFormat) {
+    let written = graph.to_format(format).unwrap();
+    assert_eq!(GraphFormat::detect(&written), format);
+    let read = Graph::from_format(&written, format).unwrap();
+    assert_eq!(read.to_value().unwrap(), serde_json::from_str::<serde_json::Value>(MULTIPLY_JSON).unwrap());
+  }
+
+  #[cfg(feature = "binary")]
+  #[test]
+  fn binary_graphs_round_trip() {
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add).add(Multiply);
+    let engine = Engine::new("demo@0.1.1", workers.build());
+    let graph = engine.parse_graph_json(MULTIPLY_JSON).unwrap();
+
+    let bytes = graph.to_binary().unwrap();
+    assert!(bytes.starts_with(b"D3NE"));
+    assert!(bytes.len() < MULTIPLY_JSON.len());
+    let read = engine.parse_binary(&bytes).unwrap();
+    assert_eq!(read, graph);
+    assert_eq!(read.to_value().unwrap(), serde_json::from_str::<serde_json::Value>(MULTIPLY_JSON).unwrap());
+
+    let mut newer = bytes.clone();
+    newer[4] = 99;
+    assert!(Graph::from_binary(&newer).unwrap_err().to_string().contains("version 99"));
+    assert!(Graph::from_binary(b"{}").is_err());
+
+    let report = engine.process_all(&graph.nodes, 1);
+    let outputs = crate::decode_outputs(&crate::encode_outputs(&report.outputs).unwrap()).unwrap();
+    assert_eq!(outputs.len(), report.outputs.len());
+    for (id, output) in &report.outputs {
+      assert_eq!(outputs[id], output.to_json().unwrap());
+    }
+  }
+
+  #[cfg(feature = "std-workers")]
+  #[test]
+  fn std_workers_cover_common_nodes() {
+    let mut builder = GraphBuilder::new("demo@0.1.1");
+    let a = builder.add_node("Number", json!({ "num": 7 }));
+    let b = builder.add_node("Number", json!({ "num": 2 }));
+    let div = builder.add_node("Divide", json!({}));
+    let less = builder.add_node("Less", json!({ "num2": 4 }));
+    let select = builder.add_node("Select", json!({ "then": "small", "else": "big" }));
+    let format = builder.add_node("Format", json!({ "template": "{{{ size }}} {n}" }));
+    let set = builder.add_node("JsonSet", json!({ "path": "n", "json": {} }));
+    let set2 = builder.add_node("JsonSet", json!({ "path": "size" }));
+    builder
+      .add_connection((a, "num"), (div, "num"))
+      .add_connection((b, "num"), (div, "num2"))
+      .add_connection((div, "num"), (less, "num"))
+      .add_connection((div, "num"), (set, "value"))
+      .add_connection((set, "json"), (set2, "json"))
+      .add_connection((less, "bool"), (select, "condition"))
+      .add_connection((select, "value"), (set2, "value"))
+      .add_connection((set2, "json"), (format, "values"));
+    let nodes = builder.build().unwrap();
+
+    let mut workers = WorkersBuilder::new();
+    crate::std_workers::register(&mut workers);
+    let engine = Engine::new("demo@0.1.1", workers.build());
+    let report = engine.process_all(&nodes, a);
+    assert!(report.is_ok(), "{:?}", report.errors);
+    assert_eq!(report.outputs[&div].to_json().unwrap(), json!({ "num": 3.5 }));
+    assert_eq!(report.outputs[&format].to_json().unwrap(), json!({ "text": "{small} 3.5" }));
+
+    let call = |name: &str, data: serde_json::Value| {
+      let node: Node = serde_json::from_value(json!({ "id": 1, "name": name, "data": data })).unwrap();
+      engine_call(name, &node).map(|o| o.to_json().unwrap())
+    };
+    let items = json!([{ "id": 1, "on": true }, { "id": 2, "on": false }, { "id": 3, "on": true }]);
+    assert_eq!(call("ArrayFilter", json!({ "array": items, "path": "on" })).unwrap(), json!({ "array": [{ "id": 1, "on": true }, { "id": 3, "on": true }] }));
+    assert_eq!(call("ArrayMap", json!({ "array": items, "path": "id" })).unwrap(), json!({ "array": [1, 2, 3] }));
+    assert_eq!(call("ArrayLength", json!({ "array": items })).unwrap(), json!({ "num": 3 }));
+    assert_eq!(call("JsonGet", json!({ "json": items, "path": "1.id" })).unwrap(), json!({ "value": 2 }));
+    assert_eq!(call("Concat", json!({ "text": "a", "text2": 1, "separator": "-" })).unwrap(), json!({ "text": "a-1" }));
+    assert_eq!(call("And", json!({ "bool": true, "bool2": false })).unwrap(), json!({ "bool": false }));
+    assert_eq!(call("Equal", json!({ "value": [1], "value2": [1] })).unwrap(), json!({ "bool": true }));
+    assert_eq!(call("Multiply", json!({ "num": 1.5, "num2": 2 })).unwrap(), json!({ "num": 3.0 }));
+    assert!(call("Divide", json!({ "num": 1, "num2": 0 })).is_err());
+    assert!(call("Add", json!({ "num": i64::MAX, "num2": 1 })).is_err());
+  }
+
+  #[cfg(feature = "std-workers")]
+  fn engine_call(name: &str, node: &Node) -> Result<OutputData> {
+    let mut workers = WorkersBuilder::new();
+    crate::std_workers::register(&mut workers);
+    workers.build().call(name, node, InputDataBuilder::new().build())
+  }
+
+  #[cfg(feature = "std-workers")]
+  #[test]
+  fn expression_worker_binds_inputs() {
+    let mut builder = GraphBuilder::new("demo@0.1.1");
+    let a = builder.add_node("Number", json!({ "num": 4 }));
+    let b = builder.add_node("Number", json!({ "num": 0.5 }));
+    let expr = builder.add_node("Expression", json!({ "expression": "num * 2 + num2 > limit ? str(num) + '!' : items[1].id", "limit": 8, "items": [{}, { "id": 7 }] }));
+    let broken = builder.add_node("Expression", json!({ "expression": "num * (2 +" }));
+    builder
+      .add_connection((a, "num"), (expr, "num"))
+      .add_connection((b, "num"), (expr, "num2"))
+      .add_connection((a, "num"), (broken, "num"));
+    let nodes = builder.build().unwrap();
+
+    let mut workers = WorkersBuilder::new();
+    crate::std_workers::register(&mut workers);
+    let engine = Engine::new("demo@0.1.1", workers.build());
+    let report = engine.process_all(&nodes, a);
+    assert_eq!(report.outputs[&expr].to_json().unwrap(), json!({ "value": "4!" }));
+    let error = report.errors[&broken].to_string();
+    assert!(error.starts_with(&format!("Node[{}]: Expression syntax error at 10", broken)), "{}", error);
+
+    let parsed = crate::Expression::parse("min(a.0.1, 3) - -b % 2").unwrap();
+    assert_eq!(parsed.variables(), vec!["a", "b"]);
+    let vars = [("a".to_string(), json!([[1, 5]])), ("b".to_string(), json!(3))].into_iter().collect();
+    assert_eq!(parsed.eval(&vars).unwrap(), json!(4));
+    assert_eq!(crate::Expression::parse("a +* b").unwrap_err().position, 3);
+    assert_eq!(crate::Expression::parse("nope(1)").unwrap_err().message, "unknown function `nope`");
+    for deep in [format!("{}1", "(".repeat(100_000)), format!("{}1", "-".repeat(100_000)), format!("a{}", ".b".repeat(100_000))] {
+      assert!(crate::Expression::parse(&deep).unwrap_err().message.contains("nested deeper"));
+    }
+    assert!(crate::Expression::parse(&format!("{}1{}", "(".repeat(30), ")".repeat(30))).is_ok());
+    let deep = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+    assert!(crate::Expression::parse(&deep).unwrap_err().message.contains("nested deeper"));
+    let long = crate::Expression::parse(&format!("1{}", "+1".repeat(100_000))).unwrap();
+    assert_eq!(long.eval(&std::collections::HashMap::new()).unwrap(), json!(100_001));
+    let mixed = crate::Expression::parse(&format!("2{} > 3 && true || x", " * 1 + 1".repeat(70))).unwrap();
+    assert_eq!(mixed.eval(&std::collections::HashMap::new()).unwrap(), json!(true));
+
+    let mut other = GraphBuilder::new("demo@0.1.1");
+    other.add_node("Number", json!({ "num": 4 }));
+    other.add_node("Number", json!({ "num": 0.5 }));
+    other.add_node("Expression", json!({ "expression": "num * 2 + num2 > limit ? str(num) + '!' : items[1].id", "limit": 20, "items": [{}, { "id": 7 }] }));
+    other.add_connection((a, "num"), (expr, "num")).add_connection((b, "num"), (expr, "num2"));
+    let report = engine.process_all_with(&other.build().unwrap(), a, RunContext::new().graph_id("other"));
+    assert_eq!(report.outputs[&expr].to_json().unwrap(), json!({ "value": 7 }));
+  }
+
+  #[cfg(feature = "script")]
+  #[test]
+  fn script_worker_runs_sandboxed() {
+    let mut builder = GraphBuilder::new("demo@0.1.1");
+    let a = builder.add_node("Number", json!({ "num": 4 }));
+    let script = builder.add_node("Script", json!({ "script": "let total = 0; for i in 0..num { total += i * factor; } #{ num: total, text: `sum ${total}` }", "factor": 2 }));
+    builder.add_connection((a, "num"), (script, "num"));
+    let nodes = builder.build().unwrap();
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(crate::ScriptWorker::new());
+    let engine = Engine::new("demo@0.1.1", workers.build());
+    let report = engine.process_all(&nodes, a);
+    assert!(report.is_ok(), "{:?}", report.errors);
+    assert_eq!(report.outputs[&script].to_json().unwrap(), json!({ "num": 12, "text": "sum 12" }));
+
+    let run = |source: &str| {
+      let node: Node = serde_json::from_value(json!({ "id": 1, "name": "Script", "data": { "script": source } })).unwrap();
+      let mut workers = WorkersBuilder::new();
+      workers.add(crate::ScriptWorker::with_limits(crate::ScriptLimits { max_operations: 1_000, ..Default::default() }));
+      workers.build().call("Script", &node, InputDataBuilder::new().build()).map(|o| o.to_json().unwrap())
+    };
+    assert!(run("loop {}").unwrap_err().to_string().contains("Too many operations"));
+    assert!(run("fn f(n) { f(n + 1) } f(0)").is_err());
+    assert!(run("import \"/etc/passwd\" as p; #{}").is_err());
+    assert!(run("42").unwrap_err().to_string().contains("map of outputs"));
+    assert!(run("let x = ;").unwrap_err().to_string().contains("syntax error"));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn process_workers_speak_json_rpc() {
+    const CHILD: &str = r#"
+      while IFS= read -r line; do
+        id=$(printf '%s' "$line" | sed -n 's/^{"id":\([0-9]*\).*/\1/p')
+        case "$line" in
+          *'"method":"handshake"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"protocol":1,"workers":[{"name":"Double","inputs":["num"],"outputs":["num"]}]}}\n' "$id" ;;
+          *'"method":"shutdown"'*) exit 0 ;;
+          *'"crash":true'*) exit 1 ;;
+          *'"hang":true'*) sleep 1 ;;
+          *)
+            num=$(printf '%s' "$line" | sed -n 's/.*"inputs":{"num":\(-\{0,1\}[0-9][0-9]*\)}.*/\1/p')
+            if [ -z "$num" ]; then
+              printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32000,"message":"num is not a number"}}\n' "$id"
+            else
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"outputs":{"num":%s}}}\n' "$id" $((num * 2))
+            fi ;;
+        esac
+      done
+    "#;
+    let host = crate::ProcessHost::spawn("sh", ["-c", CHILD]).unwrap();
+    assert_eq!(host.capabilities().workers[0].name, "Double");
+
+    let mut builder = GraphBuilder::new("demo@0.1.1");
+    let a = builder.add_node("Number", json!({ "num": 21 }));
+    let double = builder.add_node("Double", json!({}));
+    let crashing = builder.add_node("Double", json!({ "crash": true }));
+    builder
+      .add_connection((a, "num"), (double, "num"))
+      .add_connection((a, "num"), (crashing, "num"));
+    let nodes = builder.build().unwrap();
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number);
+    host.register(&mut workers);
+    let engine = Engine::new("demo@0.1.1", workers.build());
+
+    let report = engine.process_all(&nodes, a);
+    assert_eq!(report.outputs[&double].to_json().unwrap(), json!({ "num": 42 }));
+    assert!(report.errors[&crashing].to_string().contains("crashed"), "{}", report.errors[&crashing]);
+    assert_eq!(host.restarts(), 1);
+
+    let report = engine.process_all(&nodes, a);
+    assert_eq!(report.outputs[&double].to_json().unwrap(), json!({ "num": 42 }));
+    assert_eq!(host.restarts(), 2);
+
+    host.set_timeout(std::time::Duration::from_millis(200));
+    let hanging: Node = serde_json::from_value(json!({ "id": 8, "name": "Double", "data": { "hang": true } })).unwrap();
+    let error = engine_workers_call(&host, &hanging).unwrap_err();
+    assert!(format!("{:#}", error).contains("no answer to `work` within 200ms"), "{:#}", error);
+    assert_eq!(host.restarts(), 3);
+    assert_eq!(engine.process_all(&nodes, a).outputs[&double].to_json().unwrap(), json!({ "num": 42 }));
+
+    let node: Node = serde_json::from_value(json!({ "id": 9, "name": "Double", "data": { "num": "x" } })).unwrap();
+    let error = engine_workers_call(&host, &node).unwrap_err();
+    assert!(matches!(error.downcast_ref::<WorkerError>(), Some(WorkerError::NodeRunError(9, e)) if e.to_string() == "num is not a number (code -32000)"), "{}", error);
+  }
+
+  #[cfg(unix)]
+  fn engine_workers_call(host: &std::rc::Rc<crate::ProcessHost>, node: &Node) -> Result<OutputData> {
+    let mut workers = WorkersBuilder::new();
+    host.register(&mut workers);
+    workers.build().call("Double", node, InputDataBuilder::new().build())
+  }
+
+  #[cfg(feature = "wasm")]
+  fn wasm_module(name: &str, work: &str, memory_pages: u32) -> Vec<u8> {
+    let info = format!(r#"{{"name":"{}","inputs":["num"],"outputs":["num"]}}"#, name);
+    let output = r#"{"outputs":{"num":42}}"#;
+    let error = r#"{"error":"bad input"}"#;
+    let escape = |s: &str| s.replace('"', "\\\"");
+    wat::parse_str(format!(r#"
+      (module
+        (memory (export "memory") {pages})
+        (data (i32.const 16) "{info}")
+        (data (i32.const 256) "{output}")
+        (data (i32.const 512) "{error}")
+        (global $heap (mut i32) (i32.const 1024))
+        (func (export "d3ne_alloc") (param $len i32) (result i32)
+          global.get $heap
+          global.get $heap local.get $len i32.add global.set $heap)
+        (func $packed (param $ptr i64) (param $len i64) (result i64)
+          local.get $ptr i64.const 32 i64.shl local.get $len i64.or)
+        (func (export "d3ne_info") (result i64)
+          (call $packed (i64.const 16) (i64.const {info_len})))
+        (func (export "d3ne_work") (param $ptr i32) (param $len i32) (result i64)
+          {work})
+      )"#,
+      pages = memory_pages, info = escape(&info), output = escape(output), error = escape(error),
+      info_len = info.len(),
+      work = work.replace("OUTPUT", &format!("(call $packed (i64.const 256) (i64.const {}))", output.len()))
+        .replace("ERROR", &format!("(call $packed (i64.const 512) (i64.const {}))", error.len())),
+    )).unwrap()
+  }
+
+  #[cfg(feature = "wasm")]
+  #[test]
+  fn wasm_workers_are_sandboxed() {
+    use crate::{WasmLimits, WasmWorker};
+    let dir = std::env::temp_dir().join(format!("d3ne-wasm-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("answer.wasm"), wasm_module("Answer", "OUTPUT", 1)).unwrap();
+    std::fs::write(dir.join("reject.wasm"), wasm_module("Reject", "ERROR", 1)).unwrap();
+    std::fs::write(dir.join("spin.wasm"), wasm_module("Spin", "(loop $l (br $l)) OUTPUT", 1)).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a module").unwrap();
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number);
+    let infos = WasmWorker::load_dir(&dir, WasmLimits { fuel: 100_000, ..Default::default() }, &mut workers).unwrap();
+    assert_eq!(infos.iter().map(|i| i.name.as_str()).collect::<Vec<_>>(), vec!["Answer", "Reject", "Spin"]);
+    assert_eq!(infos[0].inputs, vec!["num"]);
+
+    let mut builder = GraphBuilder::new("demo@0.1.1");
+    let a = builder.add_node("Number", json!({ "num": 1 }));
+    let answer = builder.add_node("Answer", json!({}));
+    let reject = builder.add_node("Reject", json!({}));
+    let spin = builder.add_node("Spin", json!({}));
+    builder
+      .add_connection((a, "num"), (answer, "num"))
+      .add_connection((a, "num"), (reject, "num"))
+      .add_connection((a, "num"), (spin, "num"));
+    let nodes = builder.build().unwrap();
+    let engine = Engine::new("demo@0.1.1", workers.build());
+    let report = engine.process_all(&nodes, a);
+    assert_eq!(report.outputs[&answer].to_json().unwrap(), json!({ "num": 42 }));
+    assert_eq!(report.errors[&reject].to_string(), format!("Node[{}]: bad input", reject));
+    assert!(report.errors[&spin].to_string().contains("fuel"), "{}", report.errors[&spin]);
+
+    let limits = WasmLimits { memory_bytes: 1 << 20, ..Default::default() };
+    assert!(WasmWorker::from_bytes(&wasm_module("Big", "OUTPUT", 64), limits).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[cfg(feature = "plugins")]
+  fn compile_plugin(dir: &std::path::Path, name: &str, source: &str) -> std::path::PathBuf {
+    let src = dir.join(format!("{}.rs", name));
+    std::fs::write(&src, source).unwrap();
+    let status = std::process::Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into()))
+      .args(["--crate-type", "cdylib", "--crate-name", name, "-o"])
+      .arg(dir.join(format!("{}.{}", name, std::env::consts::DLL_EXTENSION)))
+      .arg(&src)
+      .status()
+      .unwrap();
+    assert!(status.success());
+    dir.join(format!("{}.{}", name, std::env::consts::DLL_EXTENSION))
+  }
+
+  #[cfg(feature = "plugins")]
+  #[test]
+  fn plugins_are_checked_before_registering() {
+    use crate::{Plugin, PluginError};
+    let dir = std::env::temp_dir().join(format!("d3ne-plugins-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // mirrors the layout of `PluginDeclaration`, the register function adds no workers
+    let declaration = |abi: u32, version: &str| format!(r#"
+      #[repr(C)]
+      pub struct Declaration {{ abi_version: u32, d3ne_version: &'static str, name: &'static str, register: fn(&mut ()) }}
+      fn register(_: &mut ()) {{}}
+      #[no_mangle]
+      pub static D3NE_PLUGIN: Declaration = Declaration {{ abi_version: {}, d3ne_version: "{}", name: "old", register }};
+    "#, abi, version);
+
+    let plain = compile_plugin(&dir, "plain", "#[no_mangle] pub extern \"C\" fn answer() -> i32 { 42 }");
+    let old_abi = compile_plugin(&dir, "old_abi", &declaration(crate::PLUGIN_ABI_VERSION + 1, crate::PLUGIN_D3NE_VERSION));
+    let old_version = compile_plugin(&dir, "old_version", &declaration(crate::PLUGIN_ABI_VERSION, "0.1.0"));
+    let empty = compile_plugin(&dir, "empty", &declaration(crate::PLUGIN_ABI_VERSION, crate::PLUGIN_D3NE_VERSION));
+
+    let mut workers = WorkersBuilder::new();
+    let error = Plugin::load(dir.join("missing.so"), &mut workers).unwrap_err();
+    assert!(matches!(error.downcast_ref::<PluginError>(), Some(PluginError::Load(..))));
+    let error = Plugin::load(&plain, &mut workers).unwrap_err();
+    assert!(matches!(error.downcast_ref::<PluginError>(), Some(PluginError::MissingDeclaration(..))));
+    let error = Plugin::load(&old_abi, &mut workers).unwrap_err();
+    assert!(matches!(error.downcast_ref::<PluginError>(), Some(PluginError::AbiMismatch(_, abi)) if *abi == crate::PLUGIN_ABI_VERSION + 1));
+    let error = Plugin::load(&old_version, &mut workers).unwrap_err();
+    assert_eq!(error.to_string(), format!("Plugin `{}` was built against d3ne 0.1.0, this host uses {}", old_version.display(), crate::PLUGIN_D3NE_VERSION));
+    assert!(Plugin::load_dir(&dir, &mut workers).is_err());
+    let loaded = Plugin::load(&empty, &mut workers).unwrap();
+    assert_eq!(loaded.info().path, empty);
+    assert!(loaded.info().workers.is_empty());
+    unsafe { loaded.unload() }.unwrap();
+
+    fn register(workers: &mut WorkersBuilder) {
+      workers.add(Number);
+    }
+    crate::export_plugin!("test-pack", register);
+    let mut workers = WorkersBuilder::new();
+    (D3NE_PLUGIN.register)(&mut workers);
+    assert_eq!(D3NE_PLUGIN.name, "test-pack");
+    assert!(workers.build().call("Number", &serde_json::from_value(json!({ "id": 1, "name": "Number", "data": { "num": 3 } })).unwrap(), InputDataBuilder::new().build()).is_ok());
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn observer_sees_node_events() {
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add).add(Multiply);
+    let mut engine = Engine::new("demo@0.1.1", workers.build());
+    let events = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    let seen = events.clone();
+    engine.set_observer(move |event| {
+      seen.borrow_mut().push(match event {
+        crate::NodeEvent::Started { node_id, .. } => format!("start {}", node_id),
+        crate::NodeEvent::Finished { node_id, .. } => format!("done {}", node_id),
+        crate::NodeEvent::Failed { node_id, .. } => format!("failed {}", node_id),
+        crate::NodeEvent::Skipped { node_id, .. } => format!("skip {}", node_id),
+      })
+    });
+    let graph = engine.parse_graph_json(MULTIPLY_JSON).unwrap();
+    engine.validate(&graph).unwrap();
+    engine.process_all(&graph.nodes, 1);
+    let events = events.borrow();
+    assert_eq!(events.first().map(String::as_str), Some("start 1"));
+    assert_eq!(events.iter().filter(|e| e.starts_with("start")).count(), events.iter().filter(|e| e.starts_with("done")).count());
+    assert!(events.contains(&"done 3".to_string()));
+
+    let mut unknown = graph.clone();
+    unknown.nodes.get_mut(&3).unwrap().name = "Divide".into();
+    assert_eq!(engine.validate(&unknown).unwrap_err().to_string(), "Node[3]: Worker Not Found: `Divide`");
+    assert_eq!(engine.workers().names(), vec!["Add", "Multiply", "Number"]);
+  }
+
+  #[cfg(feature = "server")]
+  #[test]
+  fn server_processes_and_streams_events() {
+    use std::io::{Read, Write};
+    use tungstenite::client::IntoClientRequest;
+    struct Panicking;
+    impl Worker for Panicking {
+      fn name(&self) -> &str {
+        "Panicking"
+      }
+
+      fn work(&self, _node: &Node, _input_data: InputData) -> Result<OutputData> {
+        panic!("worker bug")
+      }
+    }
+
+    let server = crate::EngineServer::start("127.0.0.1:0", crate::AllowedOrigins::default(), || {
+      let mut workers = WorkersBuilder::new();
+      workers.add(Number).add(Add).add(Multiply).add(Panicking);
+      Engine::new("demo@0.1.1", workers.build())
+    }).unwrap();
+    let request = |method: &str, path: &str, headers: &str, body: &str| {
+      let mut stream = std::net::TcpStream::connect(server.addr()).unwrap();
+      write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", method, path, headers, body.len(), body).unwrap();
+      let mut response = String::new();
+      stream.read_to_string(&mut response).unwrap();
+      let (head, body) = response.split_once("\r\n\r\n").unwrap();
+      (head.split(' ').nth(1).unwrap().to_string(), head.to_string(), serde_json::from_str::<serde_json::Value>(body).unwrap())
+    };
+
+    let (status, head, components) = request("GET", "/components", "", "");
+    assert_eq!(status, "200");
+    assert!(!head.contains("Access-Control-Allow-Origin"));
+    assert_eq!(components.as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect::<Vec<_>>(), vec!["Add", "Multiply", "Number", "Panicking"]);
+
+    let (mut events, _) = tungstenite::connect(format!("ws://{}/events", server.addr())).unwrap();
+    let graph: serde_json::Value = serde_json::from_str(MULTIPLY_JSON).unwrap();
+    let (status, head, report) = request("POST", "/process", "Origin: http://localhost:5173\r\n", &json!({ "graph": graph, "start": 1 }).to_string());
+    assert_eq!(status, "200");
+    assert!(head.contains("Access-Control-Allow-Origin: http://localhost:5173"), "{}", head);
+    assert_eq!(report["outputs"]["3"]["num"], json!(2));
+    assert_eq!(report["errors"], json!({}));
+
+    let mut seen = vec![];
+    loop {
+      let event: serde_json::Value = serde_json::from_str(events.read().unwrap().to_text().unwrap()).unwrap();
+      seen.push(format!("{} {}", event["type"].as_str().unwrap(), event["node"]));
+      if event["type"] == "finished" && event["node"] == 3 {
+        assert_eq!(event["outputs"]["num"], json!(2));
+        break;
+      }
+    }
+    assert_eq!(seen[0], "started 1");
+
+    let (status, _, error) = request("POST", "/process", "", r#"{"graph":{"id":"other@1.0.0","nodes":{}}}"#);
+    assert_eq!(status, "400");
+    assert!(error["error"].is_string());
+
+    let (status, head, _) = request("POST", "/process", "Origin: http://evil.example\r\n", &json!({ "graph": graph }).to_string());
+    assert_eq!(status, "403");
+    assert!(!head.contains("Access-Control-Allow-Origin"));
+    let mut hostile = format!("ws://{}/events", server.addr()).into_client_request().unwrap();
+    hostile.headers_mut().insert("Origin", "http://evil.example".parse().unwrap());
+    assert!(tungstenite::connect(hostile).is_err());
+
+    let panicking = GraphBuilder::new("demo@0.1.1").node(1, "Panicking", json!({})).build_graph().unwrap().to_value().unwrap();
+    let (status, _, error) = request("POST", "/process", "", &json!({ "graph": panicking }).to_string());
+    assert_eq!((status.as_str(), &error["error"]), ("500", &json!("worker panicked")));
+    assert_eq!(request("GET", "/components", "", "").0, "200");
+    server.stop();
+  }
+
+  #[cfg(feature = "watch")]
+  #[test]
+  fn watcher_swaps_valid_graphs_only() {
+    let dir = std::env::temp_dir().join(format!("d3ne-watch-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("flow.json"), MULTIPLY_JSON).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a graph").unwrap();
+
+    let mut workers = WorkersBuilder::new();
+    workers.add(Number).add(Add).add(Multiply);
+    let engine = Engine::new("demo@0.1.1", workers.build());
+    let mut watcher = crate::GraphWatcher::new(&dir).unwrap();
+    let reloads = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    let seen = reloads.clone();
+    watcher.set_on_reload(move |reload| seen.borrow_mut().push(match reload {
+      crate::Reload::Loaded { graph, .. } => format!("loaded {}", graph.nodes[&1].data.as_ref().unwrap()["num"]),
+      crate::Reload::Failed { .. } => "failed".to_string(),
+      crate::Reload::Removed { .. } => "removed".to_string(),
+    }));
+    assert_eq!(watcher.reload_all(&engine).unwrap(), 1);
+    let graphs = watcher.graphs();
+    let original = watcher.get("flow.json").unwrap();
+    let wait_for = |expected: &str| {
+      let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+      while reloads.borrow().last().map(String::as_str) != Some(expected) {
+        assert!(std::time::Instant::now() < deadline, "no `{}` reload in {:?}", expected, reloads.borrow());
+        watcher.wait(&engine, std::time::Duration::from_millis(100)).unwrap();
+      }
+    };
+
+    let mut unknown = original.as_ref().clone();
+    unknown.nodes.get_mut(&3).unwrap().name = "Divide".into();
+    unknown.save(dir.join("flow.json")).unwrap();
+    wait_for("failed");
+    assert_eq!(watcher.get("flow.json").unwrap(), original);
+
+    let mut changed = original.as_ref().clone();
+    changed.nodes.get_mut(&1).unwrap().data = Some(json!({ "num": 7 }));
+    changed.save(dir.join("flow.json")).unwrap();
+    wait_for("loaded 7");
+    assert_eq!(graphs.read().unwrap()[&dir.canonicalize().unwrap().join("flow.json")].nodes[&1].data, Some(json!({ "num": 7 })));
+
+    let saved = reloads.borrow().len();
+    std::fs::File::create(dir.join("flow.json")).unwrap();
+    assert_eq!(watcher.wait(&engine, std::time::Duration::from_millis(20)).unwrap(), 0);
+    changed.nodes.get_mut(&1).unwrap().data = Some(json!({ "num": 9 }));
+    changed.save(dir.join("flow.json")).unwrap();
+    wait_for("loaded 9");
+    assert_eq!(reloads.borrow()[saved..], ["loaded 9"]);
+
+    std::fs::remove_file(dir.join("flow.json")).unwrap();
+    wait_for("removed");
+    assert!(graphs.read().unwrap().is_empty());
+    assert_eq!(reloads.borrow()[0], "loaded 2");
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn output_values_keep_the_raw_api() {
+    #[derive(Debug, Clone, Serialize)]
+    struct Reading(f64);
+
+    let output = OutputDataBuilder::new()
+      .data("reading", Box::new(Reading(0.5)))
+      .build();
+    assert_eq!(output["reading"].try_clone().unwrap(), output["reading"].0);
+    assert!(output["reading"].data().is::<Reading>());
+
+    let raw = IOData::new(Box::new(7u8));
+    assert_eq!(raw.data().downcast_ref::<u8>(), Some(&7));
+    assert!(raw.is::<u8>());
+  }
+
+  #[test]
+  fn node_positions_round_trip() {
+    let json = json!({ "id": 1, "name": "Number", "position": [0.1, -20] });
+    let mut node: Node = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(node.position, Some(vec![0.1, -20.0]));
+    assert_eq!(serde_json::to_value(&node).unwrap(), json);
+    node.position = Some(vec![f64::NAN, 0.0]);
+    assert!(serde_json::to_value(&node).is_err());
+  }
+
+  #[test]
+  fn inputs_take_several_connections() {
+    let mut graph = GraphBuilder::new("demo@0.1.0")
+      .node(1, "Number", json!({ "num": 2 }))
+      .node(2, "Number", json!({ "num": 3 }))
+      .node(3, "Log", json!({}))
+      .connect((1, "num"), (3, "action"))
+      .connect((2, "num"), (3, "action"))
+      .build_graph()
+      .unwrap();
+    assert_eq!(graph.nodes[&3].inputs.as_ref().unwrap()["action"].connections.len(), 2);
+    assert!(matches!(graph.connect((1, "num"), (3, "action")), Err(GraphError::ConnectionExists(1, _, 3, _))));
+    graph.validate().unwrap();
+
+    let occupied = GraphBuilder::new("demo@0.1.0")
+      .node(1, "Number", json!({ "num": 2 }))
+      .node(2, "Number", json!({ "num": 3 }))
+      .node(3, "Add", json!({}))
+      .connect((1, "num"), (3, "num"))
+      .connect((2, "num"), (3, "num"))
+      .build_graph();
+    assert!(occupied.unwrap_err().to_string().contains("already has a connection"));
+
+    graph.nodes.get_mut(&3).unwrap().inputs.as_mut().unwrap().inner_mut().get_mut("action").unwrap().connections.clear();
+    assert!(graph.disconnect((1, "num"), (3, "action")).is_err());
+    assert_eq!(graph.nodes[&1].outputs.as_ref().unwrap()["num"].connections.len(), 1);
+  }
+
   struct Number;
   impl Worker for Number {
     fn name(&self) -> &str {
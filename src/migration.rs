@@ -0,0 +1,113 @@
+use crate::node::Node;
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+
+type JsonTransform = Box<dyn Fn(&mut Value) -> Result<()>>;
+type NodesTransform = Box<dyn Fn(&mut HashMap<i64, Node>) -> Result<()>>;
+
+enum Transform {
+    Json(JsonTransform),
+    Nodes(NodesTransform),
+}
+
+/// Upgrades a saved graph from one engine id to the next
+pub struct Migration {
+    pub from: String,
+    pub to: String,
+    transform: Transform,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppliedMigration {
+    pub from: String,
+    pub to: String,
+}
+
+impl Migration {
+    /// Transforms the whole editor json
+    pub fn json<F>(from: &str, to: &str, transform: F) -> Migration
+    where
+        F: Fn(&mut Value) -> Result<()> + 'static,
+    {
+        Migration {
+            from: from.to_string(),
+            to: to.to_string(),
+            transform: Transform::Json(Box::new(transform)),
+        }
+    }
+
+    /// Transforms the parsed nodes, everything else in the document is left alone
+    pub fn nodes<F>(from: &str, to: &str, transform: F) -> Migration
+    where
+        F: Fn(&mut HashMap<i64, Node>) -> Result<()> + 'static,
+    {
+        Migration {
+            from: from.to_string(),
+            to: to.to_string(),
+            transform: Transform::Nodes(Box::new(transform)),
+        }
+    }
+
+    /// Runs only this migration and sets the graph id to `to`
+    pub fn apply(&self, mut value: Value) -> Result<Value> {
+        let id = value["id"].as_str().unwrap_or_default();
+        if id != self.from {
+            bail!(
+                "Migration {} -> {} can't run on `{}`",
+                self.from,
+                self.to,
+                id
+            );
+        }
+        match &self.transform {
+            Transform::Json(transform) => transform(&mut value)?,
+            Transform::Nodes(transform) => {
+                let mut nodes: HashMap<i64, Node> = serde_json::from_value(value["nodes"].take())?;
+                transform(&mut nodes)?;
+                value["nodes"] = serde_json::to_value(nodes)?;
+            }
+        }
+        value["id"] = Value::String(self.to.clone());
+        Ok(value)
+    }
+}
+
+#[derive(Default)]
+pub struct Migrations(Vec<Migration>);
+
+impl Migrations {
+    pub fn new() -> Migrations {
+        Migrations::default()
+    }
+
+    pub fn add(&mut self, migration: Migration) -> &mut Self {
+        self.0.push(migration);
+        self
+    }
+
+    pub fn has_migration(&self, from: &str) -> bool {
+        self.0.iter().any(|m| m.from == from)
+    }
+
+    /// Follows the chain of migrations starting at the graph id until none is left
+    pub fn migrate(&self, mut value: Value) -> Result<(Value, Vec<AppliedMigration>)> {
+        let mut applied: Vec<AppliedMigration> = vec![];
+        while let Some(migration) = value["id"]
+            .as_str()
+            .and_then(|id| self.0.iter().find(|m| m.from == id))
+        {
+            if applied.iter().any(|a| a.from == migration.from) {
+                bail!("Migration cycle at `{}`", migration.from);
+            }
+            value = migration
+                .apply(value)
+                .map_err(|e| anyhow!("Migration {} -> {}: {}", migration.from, migration.to, e))?;
+            applied.push(AppliedMigration {
+                from: migration.from.clone(),
+                to: migration.to.clone(),
+            });
+        }
+        Ok((value, applied))
+    }
+}
@@ -1,28 +1,134 @@
+use crate::conversion::Conversions;
 use crate::target::{Inputs, Outputs};
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
-use serde_json::{Number, Value};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
+use std::fmt::{self, Debug};
 use std::ops::Deref;
 use std::rc::Rc;
 use thiserror::Error;
 
-#[derive(Debug)]
-pub struct IOData {
-    pub data: Box<dyn Any>,
+/// Value that can flow between sockets, anything `Debug + Clone + Serialize` gets it through
+/// the blanket impl. Two values are equal when they have the same type and the same json
+pub trait NodeValue: Any + Debug {
+    fn as_any(&self) -> &dyn Any;
+    fn clone_value(&self) -> Box<dyn NodeValue>;
+    fn eq_value(&self, other: &dyn NodeValue) -> bool;
+    fn to_json(&self) -> serde_json::Result<Value>;
+}
+
+impl<T> NodeValue for T
+where
+    T: Any + Debug + Clone + Serialize,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_value(&self) -> Box<dyn NodeValue> {
+        Box::new(self.clone())
+    }
+
+    fn eq_value(&self, other: &dyn NodeValue) -> bool {
+        other.as_any().downcast_ref::<T>().is_some_and(|other| {
+            match (serde_json::to_value(self), serde_json::to_value(other)) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => false,
+            }
+        })
+    }
+
+    fn to_json(&self) -> serde_json::Result<Value> {
+        serde_json::to_value(self)
+    }
+}
+
+impl Clone for Box<dyn NodeValue> {
+    fn clone(&self) -> Self {
+        self.clone_value()
+    }
+}
+
+impl PartialEq for dyn NodeValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_value(other)
+    }
+}
+
+pub enum IOData {
+    Value(Box<dyn NodeValue>),
+    /// Escape hatch for values that can't implement [`NodeValue`], these can't be cloned,
+    /// compared or converted to json
+    Raw(Box<dyn Any>),
 }
 
 #[allow(dead_code)]
 impl IOData {
+    /// Wraps a raw value, like the `IOData { data }` of earlier versions
+    pub fn new(data: Box<dyn Any>) -> IOData {
+        IOData::Raw(data)
+    }
+
+    /// The stored value whichever variant holds it, like the former `data` field
+    pub fn data(&self) -> &dyn Any {
+        self.as_any()
+    }
+
     pub fn is<B: Any>(&self) -> bool {
-        TypeId::of::<B>() == (*self.data).type_id()
+        TypeId::of::<B>() == self.as_any().type_id()
     }
     pub fn get<A>(&self) -> Option<&A>
     where
         A: 'static,
     {
-        return self.data.downcast_ref::<A>();
+        self.as_any().downcast_ref::<A>()
+    }
+
+    pub fn as_any(&self) -> &dyn Any {
+        match self {
+            IOData::Value(value) => value.as_any(),
+            IOData::Raw(raw) => raw.as_ref(),
+        }
+    }
+
+    pub fn value(&self) -> Option<&dyn NodeValue> {
+        match self {
+            IOData::Value(value) => Some(value.as_ref()),
+            IOData::Raw(_) => None,
+        }
+    }
+
+    pub fn try_clone(&self) -> Option<IOData> {
+        self.value().map(|value| IOData::Value(value.clone_value()))
+    }
+
+    pub fn to_json(&self) -> Result<Value> {
+        match self {
+            IOData::Value(value) => Ok(value.to_json()?),
+            IOData::Raw(_) => Err(anyhow!(NodeError::ConversionError(
+                "Raw values can't be converted to json".to_owned()
+            ))),
+        }
+    }
+}
+
+impl Debug for IOData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IOData::Value(value) => value.fmt(f),
+            IOData::Raw(raw) => f.debug_tuple("Raw").field(raw).finish(),
+        }
+    }
+}
+
+impl PartialEq for IOData {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.value(), other.value()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
     }
 }
 
@@ -36,7 +142,7 @@ pub enum NodeError {
     DeserializeError(String, String, serde_json::Error),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct NodeResult(pub IOData);
 
 impl Deref for NodeResult {
@@ -57,7 +163,7 @@ impl From<Rc<HashMap<String, NodeResult>>> for OutputData {
 }
 
 pub struct OutputDataBuilder<'a> {
-    data: Vec<(&'a str, Box<dyn Any>)>,
+    data: Vec<(&'a str, IOData)>,
 }
 
 impl<'a> OutputDataBuilder<'a> {
@@ -65,13 +171,23 @@ impl<'a> OutputDataBuilder<'a> {
         OutputDataBuilder { data: vec![] }
     }
 
-    pub fn add_data(&mut self, key: &'a str, data: Box<dyn Any>) -> &mut Self {
-        self.data.push((key, data));
+    pub fn add_data<V: NodeValue>(&mut self, key: &'a str, data: Box<V>) -> &mut Self {
+        self.data.push((key, IOData::Value(data)));
         self
     }
 
-    pub fn data(mut self, key: &'a str, data: Box<dyn Any>) -> Self {
-        self.data.push((key, data));
+    pub fn data<V: NodeValue>(mut self, key: &'a str, data: Box<V>) -> Self {
+        self.data.push((key, IOData::Value(data)));
+        self
+    }
+
+    pub fn add_raw(&mut self, key: &'a str, data: Box<dyn Any>) -> &mut Self {
+        self.data.push((key, IOData::Raw(data)));
+        self
+    }
+
+    pub fn raw(mut self, key: &'a str, data: Box<dyn Any>) -> Self {
+        self.data.push((key, IOData::Raw(data)));
         self
     }
 
@@ -79,7 +195,7 @@ impl<'a> OutputDataBuilder<'a> {
         OutputData(Rc::new(
             self.data
                 .into_iter()
-                .map(|(key, data)| (key.into(), NodeResult(IOData { data })))
+                .map(|(key, data)| (key.into(), NodeResult(data)))
                 .collect::<HashMap<_, _>>(),
         ))
     }
@@ -91,6 +207,17 @@ impl<'a> Default for OutputDataBuilder<'a> {
     }
 }
 
+impl OutputData {
+    /// Converts every output to json, fails if one of them is a raw value
+    pub fn to_json(&self) -> Result<Value> {
+        self.0
+            .iter()
+            .map(|(key, result)| Ok((key.clone(), result.to_json()?)))
+            .collect::<Result<serde_json::Map<_, _>>>()
+            .map(Value::Object)
+    }
+}
+
 impl Deref for OutputData {
     type Target = Rc<HashMap<String, NodeResult>>;
     fn deref(&self) -> &Self::Target {
@@ -99,16 +226,31 @@ impl Deref for OutputData {
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
-pub struct InputData(pub HashMap<String, OutputData>);
+pub struct InputData(pub HashMap<String, OutputData>, Rc<Conversions>);
+
+impl InputData {
+    pub fn conversions(&self) -> &Conversions {
+        &self.1
+    }
+}
+
+impl Debug for InputData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("InputData").field(&self.0).finish()
+    }
+}
 
 pub struct InputDataBuilder {
     data: Vec<(String, OutputData)>,
+    conversions: Option<Rc<Conversions>>,
 }
 
 impl InputDataBuilder {
     pub fn new() -> InputDataBuilder {
-        InputDataBuilder { data: vec![] }
+        InputDataBuilder {
+            data: vec![],
+            conversions: None,
+        }
     }
 
     pub fn add_data(mut self, key: String, data: OutputData) -> InputDataBuilder {
@@ -116,12 +258,15 @@ impl InputDataBuilder {
         self
     }
 
+    pub fn conversions(mut self, conversions: Rc<Conversions>) -> InputDataBuilder {
+        self.conversions = Some(conversions);
+        self
+    }
+
     pub fn build(self) -> InputData {
         InputData(
-            self.data
-                .into_iter()
-                .map(|(key, data)| (key, data))
-                .collect::<HashMap<_, _>>(),
+            self.data.into_iter().collect::<HashMap<_, _>>(),
+            self.conversions.unwrap_or_default(),
         )
     }
 }
@@ -134,7 +279,7 @@ impl Default for InputDataBuilder {
 
 impl From<HashMap<String, OutputData>> for InputData {
     fn from(inner: HashMap<String, OutputData>) -> Self {
-        InputData(inner)
+        InputData(inner, Rc::default())
     }
 }
 
@@ -145,15 +290,82 @@ impl Deref for InputData {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Node {
     pub id: i64,
     pub name: String,
+    /// `Some(Value::Null)` when the editor wrote `"data": null`
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "present"
+    )]
     pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub group: Option<i64>,
-    pub position: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "position")]
+    pub position: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub inputs: Option<Inputs>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub outputs: Option<Outputs>,
+    /// Fields the engine doesn't know, kept for the editor
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Keeps an explicit `null` apart from a missing field
+fn present<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(d).map(Some)
+}
+
+/// Writes coordinates back the way the editor wrote them, whole numbers as integers and
+/// fractions by their shortest `f64` form. Non-finite coordinates are rejected
+pub(crate) mod position {
+    use serde::ser::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(position: &Option<Vec<f64>>, s: S) -> Result<S::Ok, S::Error> {
+        match position {
+            None => s.serialize_none(),
+            Some(position) => s.collect_seq(
+                position
+                    .iter()
+                    .map(|p| number(*p).map_err(S::Error::custom))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<f64>>, D::Error> {
+        Option::<Vec<f64>>::deserialize(d)
+    }
+
+    pub(crate) fn number(p: f64) -> Result<serde_json::Number, String> {
+        if p.fract() == 0.0 && p.abs() < i64::MAX as f64 {
+            return Ok(serde_json::Number::from(p as i64));
+        }
+        serde_json::Number::from_f64(p)
+            .ok_or_else(|| format!("coordinate {} is not a finite number", p))
+    }
+}
+
+/// Single size value written back the same way as `position`
+pub(crate) mod dimension {
+    use serde::ser::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<f64>, s: S) -> Result<S::Ok, S::Error> {
+        value
+            .map(super::position::number)
+            .transpose()
+            .map_err(S::Error::custom)?
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<f64>, D::Error> {
+        Option::<f64>::deserialize(d)
+    }
 }
 
 type Convert<A> = Box<dyn Fn(&Value) -> Result<A>>;
@@ -163,13 +375,11 @@ impl Node {
         &self,
         field: &'static str,
         inputs: &InputData,
-        def: A,
-        deref: Box<dyn Fn(&A) -> A>,
         convert: Convert<A>,
         noerr: Option<A>,
     ) -> Result<A>
     where
-        A: 'static,
+        A: Clone + 'static,
     {
         inputs
             .0
@@ -183,7 +393,16 @@ impl Node {
                         .unwrap_or_default(),
                 )
             })
-            .map(|v| Ok(v.get::<A>().map(deref).unwrap_or(def)))
+            .map(|v| {
+                inputs.conversions().convert::<A>(v).map_err(|e| {
+                    anyhow!(NodeError::ConversionError(format!(
+                        "Field: {}, Type: {}, {}",
+                        field,
+                        std::any::type_name::<A>(),
+                        e
+                    )))
+                })
+            })
             .or(self.data.clone().and_then(|d| d.get(field).map(convert)))
             .or(noerr.map(Ok))
             .unwrap_or(Err(anyhow!("{}", NodeError::NoValueFound(field.into()))))
@@ -198,8 +417,6 @@ impl Node {
         self.get_field(
             field,
             inputs,
-            i64::MIN,
-            Box::new(|r| *r),
             Box::new(|v| {
                 v.as_i64().ok_or(anyhow!(NodeError::ConversionError(format!(
                     "Field: {}, Type: {}",
@@ -220,8 +437,6 @@ impl Node {
         self.get_field(
             field,
             inputs,
-            f64::MIN,
-            Box::new(|r| *r),
             Box::new(|v| {
                 v.as_f64().ok_or(anyhow!(NodeError::ConversionError(format!(
                     "Field: {}, Type: {}",
@@ -242,8 +457,6 @@ impl Node {
         self.get_field(
             field,
             inputs,
-            String::default(),
-            Box::new(|r| r.to_owned()),
             Box::new(|v| {
                 if let Value::String(s) = v {
                     Ok(s.clone())
@@ -268,12 +481,10 @@ impl Node {
         self.get_field(
             field,
             inputs,
-            json!({}),
-            Box::new(|r| r.clone()),
-            Box::new(|v| {
+            Box::new(move |v| {
                 serde_json::from_str(v.as_str().ok_or(anyhow!(
                     "Field: {}, unable to get str value for deserialze",
-                    field.to_string()
+                    field
                 ))?)
                 .map_err(|e| {
                     anyhow!(NodeError::DeserializeError(
@@ -291,16 +502,15 @@ impl Node {
         )
     }
 
+    /// `default` is only used when the socket is unconnected and the data has no `field`, a
+    /// connected value that cannot be converted is a `ConversionError`
     pub fn get_as_json_field_or(
         &self,
         field: &'static str,
         inputs: &InputData,
         default: Option<Value>,
     ) -> Result<Value> {
-        let err = format!(
-            "Field: {}, No josn, bool, i64, f64 or String value found",
-            field.to_owned()
-        );
+        let err = format!("Field: {}, No value found", field);
         inputs
             .get(field)
             .and_then(|i| {
@@ -312,42 +522,14 @@ impl Node {
                         .unwrap_or_default(),
                 )
                 .map(|r| {
-                    let NodeResult(v) = r;
-                    if v.is::<Value>() {
-                        v.get::<Value>()
-                            .cloned()
-                            .ok_or(anyhow!(NodeError::ConversionError(
-                                "Unable to get `Value` as json field".to_owned()
-                            )))
-                    } else if v.is::<bool>() {
-                        v.get::<bool>().map(|b| Value::Bool(*b)).ok_or(anyhow!(
-                            NodeError::ConversionError(
-                                "Unable to get `bool` as json field".to_owned()
-                            )
-                        ))
-                    } else if v.is::<i64>() {
-                        v.get::<i64>()
-                            .map(|i| Number::from(*i))
-                            .map(Value::Number)
-                            .ok_or(anyhow!(NodeError::ConversionError(
-                                "Unable to get `i64` as json field".to_owned()
-                            )))
-                    } else if v.is::<f64>() {
-                        v.get::<f64>()
-                            .and_then(|f| Number::from_f64(*f))
-                            .map(Value::Number)
-                            .ok_or(anyhow!(NodeError::ConversionError(
-                                "Unable to get `i64` as json field".to_owned()
-                            )))
-                    } else if v.is::<String>() {
-                        v.get::<String>()
-                            .map(|v| Value::String(v.clone()))
-                            .ok_or(anyhow!(NodeError::ConversionError(
-                                "Unable to get `String` as json field".to_owned()
-                            )))
-                    } else {
-                        default.clone().ok_or(anyhow!(err.to_owned()))
-                    }
+                    inputs.conversions().to_json(r).map_err(|e| {
+                        anyhow!(NodeError::ConversionError(format!(
+                            "Field: {}, Type: {}, {}",
+                            field,
+                            std::any::type_name::<Value>(),
+                            e
+                        )))
+                    })
                 })
             })
             .or(self
@@ -358,6 +540,51 @@ impl Node {
             .unwrap_or(Err(anyhow!(err.to_owned())))
     }
 
+    /// Checks through the conversion registry that the value on the `field` socket can be read as
+    /// `A`, without converting it. Unconnected sockets pass
+    pub fn check_input<A: 'static>(&self, field: &str, inputs: &InputData) -> Result<()> {
+        let result = self
+            .inputs
+            .as_ref()
+            .and_then(|i| i.get(field))
+            .and_then(|i| i.connections.first())
+            .and_then(|c| inputs.get(field).and_then(|output| output.get(&c.output)));
+        match result {
+            Some(result) if !inputs.conversions().can_convert_data::<A>(result) => {
+                Err(anyhow!(NodeError::ConversionError(format!(
+                    "Field: {}, Type: {}, no conversion registered from {:?}",
+                    field,
+                    std::any::type_name::<A>(),
+                    result.0
+                ))))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Json value of every connected input, keyed by socket name
+    pub fn get_input_values(&self, inputs: &InputData) -> Result<HashMap<String, Value>> {
+        let mut values = HashMap::new();
+        for (name, input) in self.inputs.iter().flat_map(|i| i.iter()) {
+            let result = input
+                .connections
+                .first()
+                .and_then(|c| inputs.get(name).and_then(|output| output.get(&c.output)));
+            if let Some(result) = result {
+                let value = inputs.conversions().to_json(result).map_err(|e| {
+                    anyhow!(NodeError::ConversionError(format!(
+                        "Field: {}, Type: {}, {}",
+                        name,
+                        std::any::type_name::<Value>(),
+                        e
+                    )))
+                })?;
+                values.insert(name.clone(), value);
+            }
+        }
+        Ok(values)
+    }
+
     pub fn get_as_json_field(&self, field: &'static str, inputs: &InputData) -> Result<Value> {
         self.get_as_json_field_or(field, inputs, None)
     }
@@ -378,4 +605,3 @@ impl Node {
         self.get_json_field_or(field, inputs, None)
     }
 }
-
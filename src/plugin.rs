@@ -0,0 +1,45 @@
+//! Worker packs compiled as separate shared libraries
+//!
+//! A plugin crate is a `cdylib` depending on the same d3ne version as the host and declares
+//! its entry point with `export_plugin!`:
+//!
+//! ```ignore
+//! fn register(workers: &mut d3ne::WorkersBuilder) {
+//!     workers.add(Double);
+//! }
+//!
+//! d3ne::export_plugin!("math-pack", register);
+//! ```
+//!
+//! Plugins must be built with the same compiler as the host, Rust has no stable ABI. The host
+//! loads them with `Plugin::load` from the `plugins` feature and unloads them with
+//! `PluginLibrary::unload` after dropping their workers.
+use crate::workers::WorkersBuilder;
+
+/// Bumped whenever `PluginDeclaration` or the way plugins register changes
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+pub const PLUGIN_D3NE_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const PLUGIN_SYMBOL: &str = "D3NE_PLUGIN";
+
+/// Exported by every plugin as `D3NE_PLUGIN`, `abi_version` has to stay the first field
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub abi_version: u32,
+    pub d3ne_version: &'static str,
+    pub name: &'static str,
+    pub register: fn(&mut WorkersBuilder),
+}
+
+#[macro_export]
+macro_rules! export_plugin {
+    ($name:expr, $register:expr) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static D3NE_PLUGIN: $crate::PluginDeclaration = $crate::PluginDeclaration {
+            abi_version: $crate::PLUGIN_ABI_VERSION,
+            d3ne_version: $crate::PLUGIN_D3NE_VERSION,
+            name: $name,
+            register: $register,
+        };
+    };
+}
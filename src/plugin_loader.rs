@@ -0,0 +1,219 @@
+use crate::context::{Context, RunContext};
+use crate::node::*;
+use crate::plugin::*;
+use crate::workers::{Worker, WorkerInfo, WorkersBuilder};
+use anyhow::Result;
+use libloading::Library;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("Plugin `{0}`: {1}")]
+    Load(PathBuf, libloading::Error),
+    #[error("Plugin `{0}` does not export `{PLUGIN_SYMBOL}`")]
+    MissingDeclaration(PathBuf),
+    #[error("Plugin `{0}` uses plugin ABI {1}, this host supports {PLUGIN_ABI_VERSION}")]
+    AbiMismatch(PathBuf, u32),
+    #[error("Plugin `{0}` was built against d3ne {1}, this host uses {PLUGIN_D3NE_VERSION}")]
+    VersionMismatch(PathBuf, String),
+    #[error("Plugin `{0}` is still used by {1} workers")]
+    InUse(PathBuf, usize),
+    #[error("Plugin `{0}`: {1}")]
+    Unload(PathBuf, libloading::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PluginInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub workers: Vec<String>,
+}
+
+/// Worker of a plugin, keeps its library loaded. `worker` is declared first so that it is
+/// dropped before the library
+struct PluginWorker {
+    worker: Box<dyn Worker>,
+    _library: Rc<Library>,
+}
+
+/// Handle of a loaded plugin, the library stays loaded until `unload` succeeds
+///
+/// Dropping the handle without unloading keeps the library loaded until the process exits.
+#[derive(Debug)]
+pub struct PluginLibrary {
+    info: PluginInfo,
+    library: Option<Rc<Library>>,
+}
+
+impl PluginLibrary {
+    pub fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    /// Unloads the library once every worker it registered has been dropped, which happens
+    /// when the `Workers` and engines holding them are dropped. Fails with `InUse` otherwise,
+    /// and the library then stays loaded until the process exits.
+    ///
+    /// # Safety
+    ///
+    /// Outputs, errors and any other values created by the plugin's workers carry code from
+    /// the library, none of them may be alive anymore.
+    pub unsafe fn unload(mut self) -> Result<(), PluginError> {
+        let library = self.library.take().expect("loaded until unload");
+        match Rc::try_unwrap(library) {
+            Ok(library) => library
+                .close()
+                .map_err(|e| PluginError::Unload(self.info.path.clone(), e)),
+            Err(library) => {
+                let workers = Rc::strong_count(&library) - 1;
+                std::mem::forget(library);
+                Err(PluginError::InUse(self.info.path.clone(), workers))
+            }
+        }
+    }
+}
+
+impl Drop for PluginLibrary {
+    fn drop(&mut self) {
+        if let Some(library) = self.library.take() {
+            std::mem::forget(library);
+        }
+    }
+}
+
+impl Worker for PluginWorker {
+    fn name(&self) -> &str {
+        self.worker.name()
+    }
+
+    fn info(&self) -> WorkerInfo {
+        self.worker.info()
+    }
+
+    fn init(&self) -> Result<()> {
+        self.worker.init()
+    }
+
+    fn before_run(&self, context: &RunContext) -> Result<()> {
+        self.worker.before_run(context)
+    }
+
+    fn after_run(&self, context: &RunContext) -> Result<()> {
+        self.worker.after_run(context)
+    }
+
+    fn shutdown(&self) {
+        self.worker.shutdown()
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        self.worker.work(node, input_data)
+    }
+
+    fn work_with_context(
+        &self,
+        node: &Node,
+        input_data: InputData,
+        context: &Context,
+    ) -> Result<OutputData> {
+        self.worker.work_with_context(node, input_data, context)
+    }
+}
+
+/// Same major version, and the same minor version before 1.0
+fn compatible(version: &str) -> bool {
+    match (
+        semver::Version::parse(version),
+        semver::Version::parse(PLUGIN_D3NE_VERSION),
+    ) {
+        (Ok(plugin), Ok(host)) => {
+            plugin.major == host.major && (host.major > 0 || plugin.minor == host.minor)
+        }
+        _ => false,
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    /// Loads the library at `path` and adds its workers to `workers`
+    ///
+    /// Outputs and errors created by its workers carry code from the library and may outlive
+    /// every worker and engine, so the library is only unloaded by `PluginLibrary::unload`.
+    pub fn load<P: AsRef<Path>>(path: P, workers: &mut WorkersBuilder) -> Result<PluginLibrary> {
+        let path = path.as_ref().to_path_buf();
+        // Safety: running the library initialisers is the point of loading a plugin
+        let library =
+            unsafe { Library::new(&path) }.map_err(|e| PluginError::Load(path.clone(), e))?;
+        Plugin::register(library, path, workers)
+    }
+
+    /// Loads every shared library in `dir` in file name order
+    pub fn load_dir<P: AsRef<Path>>(
+        dir: P,
+        workers: &mut WorkersBuilder,
+    ) -> Result<Vec<PluginLibrary>> {
+        let mut paths = std::fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.retain(|path| {
+            path.extension()
+                .is_some_and(|e| e == std::env::consts::DLL_EXTENSION)
+        });
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| Plugin::load(path, workers))
+            .collect()
+    }
+
+    pub(crate) fn register(
+        library: Library,
+        path: PathBuf,
+        workers: &mut WorkersBuilder,
+    ) -> Result<PluginLibrary> {
+        // Safety: the symbol is a `PluginDeclaration` unless the ABI check below fails, and
+        // only its leading `abi_version` is read before that check
+        let declaration = unsafe {
+            let symbol = library
+                .get::<*const PluginDeclaration>(PLUGIN_SYMBOL.as_bytes())
+                .map_err(|_| PluginError::MissingDeclaration(path.clone()))?;
+            let declaration = *symbol;
+            let abi_version = std::ptr::read(declaration as *const u32);
+            if abi_version != PLUGIN_ABI_VERSION {
+                bail!(PluginError::AbiMismatch(path, abi_version));
+            }
+            &*declaration
+        };
+        if !compatible(declaration.d3ne_version) {
+            bail!(PluginError::VersionMismatch(
+                path,
+                declaration.d3ne_version.to_string()
+            ));
+        }
+        let name = declaration.name.to_string();
+        let register = declaration.register;
+
+        let library = Rc::new(library);
+        let mut plugin_workers = WorkersBuilder::new();
+        register(&mut plugin_workers);
+        let mut names = vec![];
+        for worker in plugin_workers.into_workers() {
+            names.push(worker.name().to_string());
+            workers.add(PluginWorker {
+                worker,
+                _library: library.clone(),
+            });
+        }
+        Ok(PluginLibrary {
+            info: PluginInfo {
+                name,
+                path,
+                workers: names,
+            },
+            library: Some(library),
+        })
+    }
+}
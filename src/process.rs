@@ -0,0 +1,290 @@
+//! Workers living in a child process, spoken to with newline delimited JSON-RPC 2.0 over stdio
+//!
+//! The engine sends `handshake` once after spawning the child, `work` for every node and a
+//! `shutdown` notification before it goes away:
+//!
+//! ```text
+//! -> {"id":1,"jsonrpc":"2.0","method":"handshake","params":{"protocol":1}}
+//! <- {"jsonrpc":"2.0","id":1,"result":{"protocol":1,"workers":[{"name":"Double","inputs":["num"],"outputs":["num"]}]}}
+//! -> {"id":2,"jsonrpc":"2.0","method":"work","params":{"worker":"Double","node":{..},"inputs":{"num":3}}}
+//! <- {"jsonrpc":"2.0","id":2,"result":{"outputs":{"num":6}}}
+//! <- {"jsonrpc":"2.0","id":2,"error":{"code":-32000,"message":"num is not a number"}}
+//! -> {"jsonrpc":"2.0","method":"shutdown"}
+//! ```
+use crate::node::*;
+use crate::workers::{Worker, WorkerInfo, WorkersBuilder};
+use anyhow::Result;
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+pub const PROCESS_PROTOCOL_VERSION: u32 = 1;
+
+/// How long the host waits for an answer before it kills the child, see `ProcessHost::set_timeout`
+pub const DEFAULT_PROCESS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What the child answered to the handshake
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub protocol: u32,
+    pub workers: Vec<WorkerInfo>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    id: Option<u64>,
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+/// The child died or broke the protocol, the host restarts it
+#[derive(Debug)]
+struct Crashed(anyhow::Error);
+
+/// `lines` is fed by a thread reading the child stdout, so that reads can time out
+struct Running {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<std::io::Result<String>>,
+}
+
+/// One child process serving every worker it announced in the handshake
+pub struct ProcessHost {
+    program: OsString,
+    args: Vec<OsString>,
+    running: RefCell<Option<Running>>,
+    capabilities: RefCell<Capabilities>,
+    next_id: Cell<u64>,
+    restarts: Cell<u32>,
+    timeout: Cell<Duration>,
+}
+
+impl ProcessHost {
+    /// Starts the child and performs the handshake
+    pub fn spawn<P, I, A>(program: P, args: I) -> Result<Rc<ProcessHost>>
+    where
+        P: Into<OsString>,
+        I: IntoIterator<Item = A>,
+        A: Into<OsString>,
+    {
+        let host = ProcessHost {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            running: RefCell::new(None),
+            capabilities: RefCell::new(Capabilities {
+                protocol: PROCESS_PROTOCOL_VERSION,
+                workers: vec![],
+            }),
+            next_id: Cell::new(1),
+            restarts: Cell::new(0),
+            timeout: Cell::new(DEFAULT_PROCESS_TIMEOUT),
+        };
+        host.start()?;
+        Ok(Rc::new(host))
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities.borrow().clone()
+    }
+
+    /// How often the child has been restarted after a crash
+    pub fn restarts(&self) -> u32 {
+        self.restarts.get()
+    }
+
+    /// Longest wait for an answer, a child taking longer is treated as crashed and restarted
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.timeout.set(timeout);
+    }
+
+    /// Adds a `ProcessWorker` for every worker the child announced
+    pub fn register(self: &Rc<Self>, workers: &mut WorkersBuilder) {
+        for info in self.capabilities.borrow().workers.iter() {
+            workers.add(ProcessWorker {
+                host: self.clone(),
+                name: info.name.clone(),
+            });
+        }
+    }
+
+    fn start(&self) -> Result<()> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start `{:?}`: {}", self.program, e))?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in stdout.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        *self.running.borrow_mut() = Some(Running {
+            child,
+            stdin,
+            lines,
+        });
+        let result = self
+            .request("handshake", json!({ "protocol": PROCESS_PROTOCOL_VERSION }))
+            .and_then(|result| Ok(serde_json::from_value::<Capabilities>(result)?));
+        let result = match result {
+            Ok(result) if result.protocol == PROCESS_PROTOCOL_VERSION => result,
+            Ok(result) => {
+                self.stop();
+                bail!(
+                    "`{:?}` speaks protocol {}, expected {}",
+                    self.program,
+                    result.protocol,
+                    PROCESS_PROTOCOL_VERSION
+                )
+            }
+            Err(e) => {
+                self.stop();
+                bail!("Handshake with `{:?}` failed: {}", self.program, e)
+            }
+        };
+        *self.capabilities.borrow_mut() = result;
+        Ok(())
+    }
+
+    fn stop(&self) {
+        if let Some(mut running) = self.running.borrow_mut().take() {
+            let _ = writeln!(
+                running.stdin,
+                "{}",
+                json!({ "jsonrpc": "2.0", "method": "shutdown" })
+            );
+            drop(running.stdin);
+            let _ = running.child.kill();
+            let _ = running.child.wait();
+        }
+    }
+
+    /// Errors are `Crashed` when the child has to be restarted
+    fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let mut running = self.running.borrow_mut();
+        let running = running
+            .as_mut()
+            .ok_or_else(|| anyhow!(Crashed(anyhow!("not running"))))?;
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        writeln!(running.stdin, "{}", request)
+            .and_then(|_| running.stdin.flush())
+            .map_err(|e| anyhow!(Crashed(anyhow!("write failed: {}", e))))?;
+        let deadline = Instant::now() + self.timeout.get();
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = match running.lines.recv_timeout(timeout) {
+                Ok(line) => line.map_err(|e| anyhow!(Crashed(anyhow!("read failed: {}", e))))?,
+                Err(RecvTimeoutError::Disconnected) => {
+                    bail!(Crashed(anyhow!("exited while handling `{}`", method)))
+                }
+                Err(RecvTimeoutError::Timeout) => bail!(Crashed(anyhow!(
+                    "no answer to `{}` within {:?}",
+                    method,
+                    self.timeout.get()
+                ))),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response: RpcResponse = serde_json::from_str(&line)
+                .map_err(|e| anyhow!(Crashed(anyhow!("invalid response: {}", e))))?;
+            if response.id != Some(id) {
+                continue;
+            }
+            return match (response.result, response.error) {
+                (_, Some(error)) => bail!("{} (code {})", error.message, error.code),
+                (Some(result), None) => Ok(result),
+                (None, None) => Ok(Value::Null),
+            };
+        }
+    }
+
+    /// Restarts a crashed child for the next call, the failed request is not sent again since
+    /// it may be what crashed the child or may not be safe to repeat
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        match self.request(method, params) {
+            Err(e) if e.is::<Crashed>() => {
+                self.stop();
+                self.restarts.set(self.restarts.get() + 1);
+                match self.start() {
+                    Ok(()) => Err(e),
+                    Err(restart) => bail!("{}, restarting it failed: {}", e, restart),
+                }
+            }
+            result => result,
+        }
+    }
+}
+
+impl std::fmt::Display for Crashed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Worker process crashed: {}", self.0)
+    }
+}
+
+impl std::error::Error for Crashed {}
+
+impl Drop for ProcessHost {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Forwards nodes to a `ProcessHost`, the child is started again after a crash
+pub struct ProcessWorker {
+    host: Rc<ProcessHost>,
+    name: String,
+}
+
+impl Worker for ProcessWorker {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn info(&self) -> WorkerInfo {
+        self.host
+            .capabilities
+            .borrow()
+            .workers
+            .iter()
+            .find(|info| info.name == self.name)
+            .cloned()
+            .unwrap_or_else(|| WorkerInfo::new(&self.name, &[], &[]))
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let inputs = node.get_input_values(&input_data)?;
+        let result = self.host.call(
+            "work",
+            json!({ "worker": self.name, "node": node, "inputs": inputs }),
+        )?;
+        let outputs = match result.get("outputs") {
+            Some(Value::Object(outputs)) => outputs,
+            _ => bail!("Worker process returned no `outputs` map"),
+        };
+        let mut builder = OutputDataBuilder::new();
+        for (name, value) in outputs {
+            builder.add_data(name, Box::new(value.clone()));
+        }
+        Ok(builder.build())
+    }
+}
@@ -0,0 +1,141 @@
+use crate::node::*;
+use crate::workers::Worker;
+use anyhow::Result;
+use rhai::module_resolvers::{DummyModuleResolver, FileModuleResolver};
+use rhai::{Dynamic, Scope, AST};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Sandbox of a `ScriptWorker`, scripts that exceed a limit fail their node
+#[derive(Clone, Debug)]
+pub struct ScriptLimits {
+    pub max_operations: u64,
+    pub max_call_depth: usize,
+    pub max_expr_depth: usize,
+    pub max_variables: usize,
+    pub max_string_size: usize,
+    pub max_array_size: usize,
+    pub max_map_size: usize,
+    /// Directory `import` may load modules from, scripts can't touch the filesystem when `None`
+    pub module_dir: Option<PathBuf>,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        ScriptLimits {
+            max_operations: 100_000,
+            max_call_depth: 32,
+            max_expr_depth: 64,
+            max_variables: 256,
+            max_string_size: 1 << 20,
+            max_array_size: 10_000,
+            max_map_size: 10_000,
+            module_dir: None,
+        }
+    }
+}
+
+/// Runs the Rhai `script` in the node data
+///
+/// Connected input sockets and the other data fields are script variables. The script has to
+/// return a map, every entry becomes an output of the same name.
+pub struct ScriptWorker {
+    engine: rhai::Engine,
+    compiled: RefCell<HashMap<i64, (String, Rc<AST>)>>,
+}
+
+impl ScriptWorker {
+    pub fn new() -> ScriptWorker {
+        ScriptWorker::with_limits(ScriptLimits::default())
+    }
+
+    pub fn with_limits(limits: ScriptLimits) -> ScriptWorker {
+        let mut engine = rhai::Engine::new();
+        engine
+            .set_max_operations(limits.max_operations)
+            .set_max_call_levels(limits.max_call_depth)
+            .set_max_expr_depths(limits.max_expr_depth, limits.max_expr_depth)
+            .set_max_variables(limits.max_variables)
+            .set_max_string_size(limits.max_string_size)
+            .set_max_array_size(limits.max_array_size)
+            .set_max_map_size(limits.max_map_size)
+            .disable_symbol("eval")
+            .on_print(|_| {})
+            .on_debug(|_, _, _| {});
+        match limits.module_dir {
+            Some(dir) => engine.set_module_resolver(FileModuleResolver::new_with_path(dir)),
+            None => engine.set_module_resolver(DummyModuleResolver::new()),
+        };
+        ScriptWorker {
+            engine,
+            compiled: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn compile(&self, node: &Node, script: &str) -> Result<Rc<AST>> {
+        if let Some((source, ast)) = self.compiled.borrow().get(&node.id) {
+            if source == script {
+                return Ok(ast.clone());
+            }
+        }
+        let ast = Rc::new(
+            self.engine
+                .compile(script)
+                .map_err(|e| anyhow!("Script syntax error: {}", e))?,
+        );
+        self.compiled
+            .borrow_mut()
+            .insert(node.id, (script.to_string(), ast.clone()));
+        Ok(ast)
+    }
+}
+
+impl Default for ScriptWorker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Worker for ScriptWorker {
+    fn name(&self) -> &str {
+        "Script"
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let script = node.get_string_field("script", &input_data)?;
+        let ast = self.compile(node, &script)?;
+
+        let mut vars: HashMap<String, Value> = match &node.data {
+            Some(Value::Object(data)) => data
+                .iter()
+                .filter(|(name, _)| *name != "script")
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            _ => HashMap::new(),
+        };
+        vars.extend(node.get_input_values(&input_data)?);
+        let mut scope = Scope::new();
+        for (name, value) in vars {
+            let value = rhai::serde::to_dynamic(value).map_err(|e| anyhow!("{}", e))?;
+            scope.push_dynamic(name, value);
+        }
+
+        let result: Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| anyhow!("Script error: {}", e))?;
+        let type_name = result.type_name();
+        let outputs = match rhai::serde::from_dynamic::<Value>(&result) {
+            Ok(Value::Object(outputs)) => outputs,
+            _ => bail!("Script must return a map of outputs, got {}", type_name),
+        };
+        let mut builder = OutputDataBuilder::new();
+        for (name, value) in &outputs {
+            builder.add_data(name, Box::new(value.clone()));
+        }
+        Ok(builder.build())
+    }
+}
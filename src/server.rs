@@ -0,0 +1,338 @@
+//! Local HTTP server for the Rete editor, hosting one `Engine` with its workers
+//!
+//! - `GET /components` lists the `WorkerInfo` of every registered worker
+//! - `POST /process` takes `{"graph":{..},"start":1,"params":{..},"graph_id":"flow"}` and answers
+//!   with `{"outputs":{"1":{..}},"errors":{"2":"..."},"skipped":[3],"disabled":[4]}`, `start`
+//!   defaults to `Graph::entry_node` and `params` to `null`. `graph_id` keys the node state
+//!   kept between requests, without it the run starts from empty state that is dropped after
+//! - `GET /events` upgrades to a WebSocket receiving every node event as json, like
+//!   `{"type":"finished","run":1,"node":2,"worker":"Add","outputs":{"num":3},"elapsed_ms":0.1}`
+//!
+//! Browsers may only call the server from the pages in `AllowedOrigins`, a worker panicking
+//! answers its request with a 500 and a client not reading its events is disconnected.
+use crate::context::{NodeEvent, RunContext};
+use crate::engine::{Engine, ProcessReport};
+use anyhow::Result;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tiny_http::{Header, Method, ReadWrite, Request, Response, Server, StatusCode};
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+type Socket = WebSocket<Box<dyn ReadWrite + Send>>;
+
+/// Events waiting for a WebSocket client before it is disconnected as stalled
+const EVENT_BACKLOG: usize = 1024;
+
+/// Web pages allowed to call the server, checked against the `Origin` header browsers send.
+/// Requests without one, like those of `curl`, are always served
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AllowedOrigins {
+    /// Pages served from `localhost`, `127.0.0.1` or `[::1]`, on any port
+    #[default]
+    Localhost,
+    /// Exact origins like `https://editor.example.com`, an empty list rejects every page
+    List(Vec<String>),
+}
+
+impl AllowedOrigins {
+    pub fn allows(&self, origin: &str) -> bool {
+        match self {
+            AllowedOrigins::Localhost => {
+                let host = match origin.split_once("://") {
+                    Some(("http" | "https", host)) => host,
+                    _ => return false,
+                };
+                let host = match host.rsplit_once(':') {
+                    Some((host, port)) if port.parse::<u16>().is_ok() => host,
+                    _ => host,
+                };
+                matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+            }
+            AllowedOrigins::List(origins) => origins.iter().any(|allowed| allowed == origin),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProcessRequest {
+    graph: Value,
+    /// Key of the node state kept between requests
+    graph_id: Option<String>,
+    start: Option<i64>,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Serves requests on a background thread until stopped or dropped
+pub struct EngineServer {
+    server: Arc<Server>,
+    addr: SocketAddr,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EngineServer {
+    /// Binds `addr` and builds the engine with `engine` on the server thread, engines are not `Send`
+    pub fn start<A, F>(addr: A, origins: AllowedOrigins, engine: F) -> Result<EngineServer>
+    where
+        A: ToSocketAddrs,
+        F: FnOnce() -> Engine<'static> + Send + 'static,
+    {
+        let server = Arc::new(Server::http(addr).map_err(|e| anyhow!("{}", e))?);
+        let addr = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| anyhow!("Server is not listening on an ip address"))?;
+        let (ready, started) = mpsc::channel();
+        let thread = {
+            let server = server.clone();
+            std::thread::spawn(move || serve(&server, &origins, engine(), ready))
+        };
+        started
+            .recv()
+            .map_err(|_| anyhow!("Server thread panicked while building the engine"))?;
+        Ok(EngineServer {
+            server,
+            addr,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stops accepting requests and waits for the server thread
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for EngineServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn serve(
+    server: &Server,
+    origins: &AllowedOrigins,
+    mut engine: Engine<'static>,
+    ready: mpsc::Sender<()>,
+) {
+    let clients = Rc::new(RefCell::new(Vec::<SyncSender<Message>>::new()));
+    {
+        let clients = clients.clone();
+        engine.set_observer(move |event| {
+            let message = Message::Text(event_json(event).to_string());
+            clients
+                .borrow_mut()
+                .retain(|client| client.try_send(message.clone()).is_ok());
+        });
+    }
+    let _ = ready.send(());
+    for mut request in server.incoming_requests() {
+        let origin = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Origin"))
+            .map(|h| h.value.to_string());
+        if origin
+            .as_deref()
+            .is_some_and(|origin| !origins.allows(origin))
+        {
+            let forbidden = json_response(403, json!({ "error": "origin not allowed" }));
+            let _ = request.respond(forbidden);
+            continue;
+        }
+        let method = request.method().clone();
+        let path = request.url().split('?').next().unwrap_or("").to_string();
+        if (&method, path.as_str()) == (&Method::Get, "/events") {
+            if let Some(socket) = upgrade(request, origin.as_deref()) {
+                clients.borrow_mut().push(send_events(socket));
+            }
+            continue;
+        }
+        let response = panic::catch_unwind(AssertUnwindSafe(|| {
+            route(&engine, &method, &path, &mut request)
+        }))
+        .unwrap_or_else(|_| json_response(500, json!({ "error": "worker panicked" })));
+        respond(request, origin.as_deref(), response);
+    }
+}
+
+fn route(
+    engine: &Engine,
+    method: &Method,
+    path: &str,
+    request: &mut Request,
+) -> Response<Cursor<Vec<u8>>> {
+    match (method, path) {
+        (Method::Options, _) => Response::from_data(vec![]).with_status_code(204),
+        (Method::Get, "/components") => {
+            let body = serde_json::to_value(engine.workers().infos());
+            json_response(200, body.unwrap_or(Value::Null))
+        }
+        (Method::Post, "/process") => match process(engine, request.as_reader()) {
+            Ok(body) => json_response(200, body),
+            Err(e) => json_response(400, json!({ "error": e.to_string() })),
+        },
+        _ => json_response(404, json!({ "error": "not found" })),
+    }
+}
+
+/// Writes events on a thread of their own, so that a stalled client only stalls that thread
+fn send_events(mut socket: Socket) -> SyncSender<Message> {
+    let (sender, events) = mpsc::sync_channel::<Message>(EVENT_BACKLOG);
+    std::thread::spawn(move || {
+        for message in events {
+            if socket.send(message).is_err() {
+                return;
+            }
+        }
+        let _ = socket.close(None);
+        let _ = socket.flush();
+    });
+    sender
+}
+
+fn process(engine: &Engine, body: &mut dyn std::io::Read) -> Result<Value> {
+    let request: ProcessRequest = serde_json::from_reader(body)?;
+    let graph = engine.parse_graph(request.graph)?;
+    engine.validate(&graph)?;
+    let start = match request.start {
+        Some(start) if graph.nodes.contains_key(&start) => start,
+        Some(start) => bail!("Start node {} does not exist", start),
+        None => graph
+            .entry_node()
+            .ok_or_else(|| anyhow!("Graph has no node without inputs, pass `start`"))?,
+    };
+    let context = RunContext::new().params(request.params);
+    let Some(graph_id) = request.graph_id else {
+        // private to this run, its state is dropped afterwards
+        let graph_id = format!("\0request-{}", context.run_id);
+        let report = engine.process_all_with(&graph.nodes, start, context.graph_id(&graph_id));
+        engine.reset_graph_state(&graph_id)?;
+        return report_json(&report);
+    };
+    let report = engine.process_all_with(&graph.nodes, start, context.graph_id(&graph_id));
+    report_json(&report)
+}
+
+fn report_json(report: &ProcessReport) -> Result<Value> {
+    let outputs = report
+        .outputs
+        .iter()
+        .map(|(id, output)| Ok((id.to_string(), output.to_json()?)))
+        .collect::<Result<BTreeMap<_, _>>>()?;
+    let errors = report
+        .errors
+        .iter()
+        .map(|(id, error)| (id.to_string(), error.to_string()))
+        .collect::<BTreeMap<_, _>>();
+    Ok(json!({
+        "outputs": outputs,
+        "errors": errors,
+        "skipped": report.skipped,
+        "disabled": report.disabled,
+    }))
+}
+
+fn event_json(event: &NodeEvent) -> Value {
+    match event {
+        NodeEvent::Started {
+            run_id,
+            node_id,
+            worker,
+        } => json!({ "type": "started", "run": run_id, "node": node_id, "worker": worker }),
+        NodeEvent::Finished {
+            run_id,
+            node_id,
+            worker,
+            outputs,
+            elapsed,
+        } => json!({
+            "type": "finished",
+            "run": run_id,
+            "node": node_id,
+            "worker": worker,
+            "outputs": outputs.to_json().unwrap_or(Value::Null),
+            "elapsed_ms": elapsed.as_secs_f64() * 1000.0,
+        }),
+        NodeEvent::Failed {
+            run_id,
+            node_id,
+            worker,
+            error,
+            elapsed,
+        } => json!({
+            "type": "failed",
+            "run": run_id,
+            "node": node_id,
+            "worker": worker,
+            "error": error.to_string(),
+            "elapsed_ms": elapsed.as_secs_f64() * 1000.0,
+        }),
+        NodeEvent::Skipped { run_id, node_id } => {
+            json!({ "type": "skipped", "run": run_id, "node": node_id })
+        }
+    }
+}
+
+fn upgrade(request: Request, origin: Option<&str>) -> Option<Socket> {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| tungstenite::handshake::derive_accept_key(h.value.as_str().trim().as_bytes()));
+    let Some(accept) = key else {
+        respond(
+            request,
+            origin,
+            json_response(400, json!({ "error": "expected a WebSocket upgrade" })),
+        );
+        return None;
+    };
+    let response =
+        Response::empty(StatusCode(101)).with_header(header("Sec-WebSocket-Accept", &accept));
+    let stream = request.upgrade("websocket", response);
+    Some(WebSocket::from_raw_socket(stream, Role::Server, None))
+}
+
+fn json_response(status: u16, body: Value) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+/// Answers with the CORS headers the editor needs when served from another allowed origin
+fn respond<R: std::io::Read>(request: Request, origin: Option<&str>, response: Response<R>) {
+    let response = match origin {
+        Some(origin) => response
+            .with_header(header("Access-Control-Allow-Origin", origin))
+            .with_header(header("Access-Control-Allow-Methods", "GET, POST, OPTIONS"))
+            .with_header(header("Access-Control-Allow-Headers", "Content-Type"))
+            .with_header(header("Vary", "Origin")),
+        None => response,
+    };
+    let _ = request.respond(response);
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("valid header")
+}
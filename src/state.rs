@@ -0,0 +1,164 @@
+use crate::node::Node;
+use anyhow::Result;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// State of the nodes of one graph, keyed by node id
+pub type StateSnapshot = BTreeMap<i64, Value>;
+
+/// Persistent per node state that survives across runs, keyed by graph id and node id so
+/// graphs sharing an engine don't see each other's state
+pub trait StateStore {
+    fn get(&self, graph_id: &str, node_id: i64) -> Result<Option<Value>>;
+    fn set(&self, graph_id: &str, node_id: i64, value: Value) -> Result<()>;
+    fn reset(&self, graph_id: &str, node_id: i64) -> Result<()>;
+    fn reset_graph(&self, graph_id: &str) -> Result<()>;
+    /// Drops the state of every graph
+    fn clear(&self) -> Result<()>;
+    fn snapshot(&self, graph_id: &str) -> Result<StateSnapshot>;
+    /// Replaces the state of the graph, other graphs keep theirs
+    fn restore(&self, graph_id: &str, snapshot: StateSnapshot) -> Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStateStore(RefCell<BTreeMap<String, StateSnapshot>>);
+
+impl MemoryStateStore {
+    pub fn new() -> MemoryStateStore {
+        MemoryStateStore::default()
+    }
+}
+
+impl StateStore for MemoryStateStore {
+    fn get(&self, graph_id: &str, node_id: i64) -> Result<Option<Value>> {
+        Ok(self
+            .0
+            .borrow()
+            .get(graph_id)
+            .and_then(|graph| graph.get(&node_id))
+            .cloned())
+    }
+
+    fn set(&self, graph_id: &str, node_id: i64, value: Value) -> Result<()> {
+        self.0
+            .borrow_mut()
+            .entry(graph_id.to_string())
+            .or_default()
+            .insert(node_id, value);
+        Ok(())
+    }
+
+    fn reset(&self, graph_id: &str, node_id: i64) -> Result<()> {
+        let mut state = self.0.borrow_mut();
+        if let Some(graph) = state.get_mut(graph_id) {
+            graph.remove(&node_id);
+            if graph.is_empty() {
+                state.remove(graph_id);
+            }
+        }
+        Ok(())
+    }
+
+    fn reset_graph(&self, graph_id: &str) -> Result<()> {
+        self.0.borrow_mut().remove(graph_id);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.0.borrow_mut().clear();
+        Ok(())
+    }
+
+    fn snapshot(&self, graph_id: &str) -> Result<StateSnapshot> {
+        Ok(self.0.borrow().get(graph_id).cloned().unwrap_or_default())
+    }
+
+    fn restore(&self, graph_id: &str, snapshot: StateSnapshot) -> Result<()> {
+        let mut state = self.0.borrow_mut();
+        if snapshot.is_empty() {
+            state.remove(graph_id);
+        } else {
+            state.insert(graph_id.to_string(), snapshot);
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the state in memory and rewrites the json file on every change
+#[derive(Debug)]
+pub struct JsonFileStateStore {
+    path: PathBuf,
+    state: MemoryStateStore,
+}
+
+impl JsonFileStateStore {
+    /// Loads the existing state from `path` if the file exists
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<JsonFileStateStore> {
+        let path = path.as_ref().to_path_buf();
+        let state = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(JsonFileStateStore {
+            path,
+            state: MemoryStateStore(RefCell::new(state)),
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::write(
+            &self.path,
+            serde_json::to_string_pretty(&*self.state.0.borrow())?,
+        )?;
+        Ok(())
+    }
+}
+
+impl StateStore for JsonFileStateStore {
+    fn get(&self, graph_id: &str, node_id: i64) -> Result<Option<Value>> {
+        self.state.get(graph_id, node_id)
+    }
+
+    fn set(&self, graph_id: &str, node_id: i64, value: Value) -> Result<()> {
+        self.state.set(graph_id, node_id, value)?;
+        self.save()
+    }
+
+    fn reset(&self, graph_id: &str, node_id: i64) -> Result<()> {
+        self.state.reset(graph_id, node_id)?;
+        self.save()
+    }
+
+    fn reset_graph(&self, graph_id: &str) -> Result<()> {
+        self.state.reset_graph(graph_id)?;
+        self.save()
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.state.clear()?;
+        self.save()
+    }
+
+    fn snapshot(&self, graph_id: &str) -> Result<StateSnapshot> {
+        self.state.snapshot(graph_id)
+    }
+
+    fn restore(&self, graph_id: &str, snapshot: StateSnapshot) -> Result<()> {
+        self.state.restore(graph_id, snapshot)?;
+        self.save()
+    }
+}
+
+/// Graph together with its node state, see `Engine::snapshot`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    pub id: String,
+    /// Key of the node state, see `RunContext::graph_id`
+    pub graph_id: String,
+    pub nodes: HashMap<i64, Node>,
+    pub state: StateSnapshot,
+}
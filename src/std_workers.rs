@@ -0,0 +1,590 @@
+//! Built-in workers for the basic nodes most graphs need, enable with the `std-workers` feature
+use crate::context::Context;
+use crate::expression::Expression;
+use crate::node::*;
+use crate::workers::{Worker, WorkerInfo, WorkersBuilder};
+use anyhow::Result;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Adds every worker of this module
+pub fn register(workers: &mut WorkersBuilder) -> &mut WorkersBuilder {
+    workers
+        .add(Number)
+        .add(Text)
+        .add(Add)
+        .add(Subtract)
+        .add(Multiply)
+        .add(Divide)
+        .add(Equal)
+        .add(Less)
+        .add(Greater)
+        .add(And)
+        .add(Or)
+        .add(Not)
+        .add(Format)
+        .add(Concat)
+        .add(JsonGet)
+        .add(JsonSet)
+        .add(ArrayMap)
+        .add(ArrayFilter)
+        .add(ArrayLength)
+        .add(Select)
+        .add(ExpressionWorker::default())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+impl Num {
+    fn read(node: &Node, field: &'static str, inputs: &InputData) -> Result<Num> {
+        let value = node.get_as_json_field(field, inputs)?;
+        value
+            .as_i64()
+            .map(Num::Int)
+            .or_else(|| value.as_f64().map(Num::Float))
+            .ok_or_else(|| anyhow!("Field: {}, expected a number, got {}", field, value))
+    }
+
+    fn float(self) -> f64 {
+        match self {
+            Num::Int(i) => i as f64,
+            Num::Float(f) => f,
+        }
+    }
+
+    fn output(self, key: &str) -> OutputData {
+        match self {
+            Num::Int(i) => OutputDataBuilder::new().data(key, Box::new(i)).build(),
+            Num::Float(f) => OutputDataBuilder::new().data(key, Box::new(f)).build(),
+        }
+    }
+}
+
+fn read_bool(node: &Node, field: &'static str, inputs: &InputData) -> Result<bool> {
+    let value = node.get_as_json_field(field, inputs)?;
+    value
+        .as_bool()
+        .ok_or_else(|| anyhow!("Field: {}, expected a bool, got {}", field, value))
+}
+
+fn read_array(node: &Node, field: &'static str, inputs: &InputData) -> Result<Vec<Value>> {
+    match node.get_as_json_field(field, inputs)? {
+        Value::Array(array) => Ok(array),
+        value => bail!("Field: {}, expected an array, got {}", field, value),
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn single<V: NodeValue>(key: &str, value: V) -> OutputData {
+    OutputDataBuilder::new().data(key, Box::new(value)).build()
+}
+
+/// Dotted path like `user.tags.0`, numeric segments index arrays
+pub fn json_get<'v>(value: &'v Value, path: &str) -> Option<&'v Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |value, segment| match value {
+            Value::Array(array) => segment.parse::<usize>().ok().and_then(|i| array.get(i)),
+            value => value.get(segment),
+        })
+}
+
+/// Sets the value at a dotted path, creating missing objects along the way
+pub fn json_set(value: &mut Value, path: &str, new: Value) -> Result<()> {
+    let mut current = value;
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        if current.is_null() {
+            *current = Value::Object(Default::default());
+        }
+        current = match current {
+            Value::Object(object) => object.entry(segment).or_insert(Value::Null),
+            Value::Array(array) => {
+                let len = array.len();
+                segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| array.get_mut(i))
+                    .ok_or_else(|| {
+                        anyhow!("Path: {}, index `{}` out of bounds {}", path, segment, len)
+                    })?
+            }
+            other => bail!("Path: {}, can't index into {}", path, other),
+        };
+    }
+    *current = new;
+    Ok(())
+}
+
+/// `num` constant from the node data
+pub struct Number;
+
+impl Worker for Number {
+    fn name(&self) -> &str {
+        "Number"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &[], &["num"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        Ok(Num::read(node, "num", &input_data)?.output("num"))
+    }
+}
+
+/// `text` constant from the node data
+pub struct Text;
+
+impl Worker for Text {
+    fn name(&self) -> &str {
+        "Text"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &[], &["text"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let text = node.get_string_field("text", &input_data)?;
+        Ok(single("text", text))
+    }
+}
+
+/// Integer operands stay integers, anything else is computed as floats
+macro_rules! arithmetic_worker {
+    ($worker:ident, $checked:ident, $op:tt) => {
+        pub struct $worker;
+
+        impl Worker for $worker {
+            fn name(&self) -> &str {
+                stringify!($worker)
+            }
+
+            fn info(&self) -> WorkerInfo {
+                WorkerInfo::new(self.name(), &["num", "num2"], &["num"])
+            }
+
+            fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+                let num = Num::read(node, "num", &input_data)?;
+                let num2 = Num::read(node, "num2", &input_data)?;
+                let result = match (num, num2) {
+                    (Num::Int(a), Num::Int(b)) => Num::Int(a.$checked(b).ok_or_else(|| {
+                        anyhow!("{} {} {} overflows", a, stringify!($op), b)
+                    })?),
+                    (a, b) => Num::Float(a.float() $op b.float()),
+                };
+                Ok(result.output("num"))
+            }
+        }
+    };
+}
+
+arithmetic_worker!(Add, checked_add, +);
+arithmetic_worker!(Subtract, checked_sub, -);
+arithmetic_worker!(Multiply, checked_mul, *);
+
+/// Stays an integer when `num2` divides `num` evenly
+pub struct Divide;
+
+impl Worker for Divide {
+    fn name(&self) -> &str {
+        "Divide"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["num", "num2"], &["num"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let num = Num::read(node, "num", &input_data)?;
+        let num2 = Num::read(node, "num2", &input_data)?;
+        if num2.float() == 0.0 {
+            bail!("Division by zero");
+        }
+        let result = match (num, num2) {
+            (Num::Int(a), Num::Int(b)) if a.checked_rem(b) == Some(0) => Num::Int(a / b),
+            (a, b) => Num::Float(a.float() / b.float()),
+        };
+        Ok(result.output("num"))
+    }
+}
+
+/// Compares `value` and `value2` as json, so `1` and `1.0` are different
+pub struct Equal;
+
+impl Worker for Equal {
+    fn name(&self) -> &str {
+        "Equal"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["value", "value2"], &["bool"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let value = node.get_as_json_field("value", &input_data)?;
+        let value2 = node.get_as_json_field("value2", &input_data)?;
+        Ok(single("bool", value == value2))
+    }
+}
+
+macro_rules! comparison_worker {
+    ($worker:ident, $op:tt) => {
+        pub struct $worker;
+
+        impl Worker for $worker {
+            fn name(&self) -> &str {
+                stringify!($worker)
+            }
+
+            fn info(&self) -> WorkerInfo {
+                WorkerInfo::new(self.name(), &["num", "num2"], &["bool"])
+            }
+
+            fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+                let num = Num::read(node, "num", &input_data)?;
+                let num2 = Num::read(node, "num2", &input_data)?;
+                let result = match (num, num2) {
+                    (Num::Int(a), Num::Int(b)) => a $op b,
+                    (a, b) => a.float() $op b.float(),
+                };
+                Ok(single("bool", result))
+            }
+        }
+    };
+}
+
+comparison_worker!(Less, <);
+comparison_worker!(Greater, >);
+
+macro_rules! logic_worker {
+    ($worker:ident, $op:tt) => {
+        pub struct $worker;
+
+        impl Worker for $worker {
+            fn name(&self) -> &str {
+                stringify!($worker)
+            }
+
+            fn info(&self) -> WorkerInfo {
+                WorkerInfo::new(self.name(), &["bool", "bool2"], &["bool"])
+            }
+
+            fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+                let a = read_bool(node, "bool", &input_data)?;
+                let b = read_bool(node, "bool2", &input_data)?;
+                Ok(single("bool", a $op b))
+            }
+        }
+    };
+}
+
+logic_worker!(And, &&);
+logic_worker!(Or, ||);
+
+pub struct Not;
+
+impl Worker for Not {
+    fn name(&self) -> &str {
+        "Not"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["bool"], &["bool"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        Ok(single("bool", !read_bool(node, "bool", &input_data)?))
+    }
+}
+
+/// Fills `{path}` placeholders in `template` from the `values` object, `{{` escapes a brace
+pub struct Format;
+
+impl Worker for Format {
+    fn name(&self) -> &str {
+        "Format"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["values"], &["text"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let template = node.get_string_field("template", &input_data)?;
+        let values = node.get_as_json_field_or("values", &input_data, Some(Value::Null))?;
+        let mut result = String::with_capacity(template.len());
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    result.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    result.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| anyhow!("Template: unclosed `{{` in `{}`", template))?;
+                    let path = rest[..end].trim();
+                    let value = json_get(&values, path)
+                        .ok_or_else(|| anyhow!("Template: no value for `{}`", path))?;
+                    result.push_str(&text(value));
+                    chars = rest[end + 1..].chars();
+                }
+                c => result.push(c),
+            }
+        }
+        Ok(single("text", result))
+    }
+}
+
+/// Joins `text` and `text2` with the optional `separator`, non strings are written as json
+pub struct Concat;
+
+impl Worker for Concat {
+    fn name(&self) -> &str {
+        "Concat"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["text", "text2"], &["text"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let a = node.get_as_json_field("text", &input_data)?;
+        let b = node.get_as_json_field("text2", &input_data)?;
+        let separator = node.get_string_field_or("separator", &input_data, Some(String::new()))?;
+        Ok(single(
+            "text",
+            format!("{}{}{}", text(&a), separator, text(&b)),
+        ))
+    }
+}
+
+/// Reads `path` from `json`, `null` when the path doesn't exist
+pub struct JsonGet;
+
+impl Worker for JsonGet {
+    fn name(&self) -> &str {
+        "JsonGet"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["json"], &["value"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let json = node.get_as_json_field("json", &input_data)?;
+        let path = node.get_string_field("path", &input_data)?;
+        let value = json_get(&json, &path).cloned().unwrap_or(Value::Null);
+        Ok(single("value", value))
+    }
+}
+
+pub struct JsonSet;
+
+impl Worker for JsonSet {
+    fn name(&self) -> &str {
+        "JsonSet"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["json", "value"], &["json"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let mut json = node.get_as_json_field_or("json", &input_data, Some(Value::Null))?;
+        let path = node.get_string_field("path", &input_data)?;
+        let value = node.get_as_json_field("value", &input_data)?;
+        json_set(&mut json, &path, value)?;
+        Ok(single("json", json))
+    }
+}
+
+/// Replaces every item of `array` with the value at `path`
+pub struct ArrayMap;
+
+impl Worker for ArrayMap {
+    fn name(&self) -> &str {
+        "ArrayMap"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["array"], &["array"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let array = read_array(node, "array", &input_data)?;
+        let path = node.get_string_field("path", &input_data)?;
+        let array = array
+            .iter()
+            .map(|item| json_get(item, &path).cloned().unwrap_or(Value::Null))
+            .collect::<Vec<_>>();
+        Ok(single("array", Value::Array(array)))
+    }
+}
+
+/// Keeps the items whose value at `path` equals `equals`, or is truthy without `equals`
+pub struct ArrayFilter;
+
+impl Worker for ArrayFilter {
+    fn name(&self) -> &str {
+        "ArrayFilter"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["array"], &["array"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let array = read_array(node, "array", &input_data)?;
+        let path = node.get_string_field_or("path", &input_data, Some(String::new()))?;
+        let equals = node.get_as_json_field("equals", &input_data).ok();
+        let array = array
+            .into_iter()
+            .filter(|item| {
+                let value = json_get(item, &path);
+                match (&equals, value) {
+                    (Some(equals), value) => value == Some(equals),
+                    (None, Some(value)) => truthy(value),
+                    (None, None) => false,
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok(single("array", Value::Array(array)))
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+pub struct ArrayLength;
+
+impl Worker for ArrayLength {
+    fn name(&self) -> &str {
+        "ArrayLength"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["array"], &["num"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let array = read_array(node, "array", &input_data)?;
+        Ok(single("num", array.len() as i64))
+    }
+}
+
+/// Outputs `then` when `condition` holds, otherwise `else`
+pub struct Select;
+
+impl Worker for Select {
+    fn name(&self) -> &str {
+        "Select"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &["condition", "then", "else"], &["value"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let field = if read_bool(node, "condition", &input_data)? {
+            "then"
+        } else {
+            "else"
+        };
+        let value = node.get_as_json_field(field, &input_data)?;
+        Ok(single("value", value))
+    }
+}
+
+/// Evaluates the `expression` in the node data, see `Expression` for the language
+///
+/// Every connected input socket binds a variable of the same name, other data fields are
+/// variables too. Expressions are parsed once per graph id and expression text.
+#[derive(Default)]
+pub struct ExpressionWorker {
+    parsed: RefCell<HashMap<(String, String), Rc<Expression>>>,
+}
+
+impl ExpressionWorker {
+    /// Parsed expressions kept before the cache starts over
+    const CACHE_LIMIT: usize = 1024;
+
+    fn expression(&self, graph_id: &str, source: &str) -> Result<Rc<Expression>> {
+        let key = (graph_id.to_string(), source.to_string());
+        if let Some(expression) = self.parsed.borrow().get(&key) {
+            return Ok(expression.clone());
+        }
+        let expression = Rc::new(Expression::parse(source)?);
+        let mut parsed = self.parsed.borrow_mut();
+        if parsed.len() >= Self::CACHE_LIMIT {
+            parsed.clear();
+        }
+        parsed.insert(key, expression.clone());
+        Ok(expression)
+    }
+
+    fn evaluate(&self, node: &Node, input_data: InputData, graph_id: &str) -> Result<OutputData> {
+        let source = node.get_string_field("expression", &input_data)?;
+        let expression = self.expression(graph_id, &source)?;
+        let mut vars: HashMap<String, Value> = match &node.data {
+            Some(Value::Object(data)) => data
+                .iter()
+                .filter(|(name, _)| *name != "expression")
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            _ => HashMap::new(),
+        };
+        vars.extend(node.get_input_values(&input_data)?);
+        Ok(single("value", expression.eval(&vars)?))
+    }
+}
+
+impl Worker for ExpressionWorker {
+    fn name(&self) -> &str {
+        "Expression"
+    }
+
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &[], &["value"])
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        self.evaluate(node, input_data, "")
+    }
+
+    fn work_with_context(
+        &self,
+        node: &Node,
+        input_data: InputData,
+        context: &Context,
+    ) -> Result<OutputData> {
+        self.evaluate(node, input_data, context.graph_id())
+    }
+}
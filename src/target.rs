@@ -1,37 +1,41 @@
 use std::{collections::HashMap, ops::Deref};
 use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct InputConnection {
   pub node: i64,
   pub output: String,
   pub data: Value
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Input {
   pub connections: Vec<InputConnection>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct OutputConnection {
   pub node: i64,
   pub input: String,
   pub data: Value
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Output {
   pub connections: Vec<OutputConnection>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct Inputs(HashMap<String, Input>);
 
 impl Inputs {
   pub fn inner(&self) -> &HashMap<String, Input> {
     &self.0
   }
+
+  pub fn inner_mut(&mut self) -> &mut HashMap<String, Input> {
+    &mut self.0
+  }
 }
 
 impl Deref for Inputs {
@@ -41,13 +45,17 @@ impl Deref for Inputs {
   }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct Outputs(HashMap<String, Output>);
 
 impl Outputs {
   pub fn inner(&self) -> &HashMap<String, Output> {
     &self.0
   }
+
+  pub fn inner_mut(&mut self) -> &mut HashMap<String, Output> {
+    &mut self.0
+  }
 }
 
 impl Deref for Outputs {
@@ -0,0 +1,83 @@
+use semver::{Version, VersionReq};
+use std::fmt;
+use std::str::FromStr;
+
+/// `name@version` id shared by engines and the graphs they load
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EngineVersion {
+    pub name: String,
+    pub version: Version,
+}
+
+impl FromStr for EngineVersion {
+    type Err = String;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        let (name, version) = id
+            .rsplit_once('@')
+            .ok_or(format!("`{}` is not in the form name@version", id))?;
+        let version = Version::parse(version).map_err(|e| format!("`{}`: {}", id, e))?;
+        Ok(EngineVersion {
+            name: name.to_string(),
+            version,
+        })
+    }
+}
+
+impl fmt::Display for EngineVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+/// Rule deciding which graph ids an engine accepts
+#[derive(Clone, Debug, Default)]
+pub enum Compatibility {
+    /// Ids have to be equal, they don't need to be semver
+    #[default]
+    Exact,
+    /// Same name and major version, graphs can't be newer than the engine
+    SameMajor,
+    /// Same name and the graph version matches the range
+    Range(VersionReq),
+}
+
+impl Compatibility {
+    /// Returns why the graph id is rejected
+    pub fn check(&self, engine: &str, graph: &str) -> Result<(), String> {
+        if let Compatibility::Exact = self {
+            return if engine == graph {
+                Ok(())
+            } else {
+                Err("exact match required".to_string())
+            };
+        }
+        let engine = engine.parse::<EngineVersion>()?;
+        let graph = graph.parse::<EngineVersion>()?;
+        if engine.name != graph.name {
+            return Err(format!(
+                "name `{}` differs from `{}`",
+                graph.name, engine.name
+            ));
+        }
+        match self {
+            Compatibility::Exact => unreachable!(),
+            Compatibility::SameMajor if graph.version.major != engine.version.major => {
+                Err(format!(
+                    "major version {} differs from {}",
+                    graph.version.major, engine.version.major
+                ))
+            }
+            Compatibility::SameMajor if graph.version > engine.version => Err(format!(
+                "graph version {} is newer than engine version {}",
+                graph.version, engine.version
+            )),
+            Compatibility::SameMajor => Ok(()),
+            Compatibility::Range(req) if !req.matches(&graph.version) => Err(format!(
+                "version {} does not match `{}`",
+                graph.version, req
+            )),
+            Compatibility::Range(_) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Workers compiled to WebAssembly, every call runs in a fresh instance without host imports
+//!
+//! A module exports its `memory` and three functions exchanging json, results are the pointer
+//! in the upper and the length in the lower 32 bits of an `i64`:
+//!
+//! - `d3ne_alloc(len: i32) -> i32` reserves `len` bytes for the engine to write to
+//! - `d3ne_info() -> i64` points at `{"name":"Double","inputs":["num"],"outputs":["num"]}`
+//! - `d3ne_work(ptr: i32, len: i32) -> i64` takes `{"node":{..},"inputs":{"num":3}}` and
+//!   returns `{"outputs":{"num":6}}` or `{"error":"num is not a number"}`
+use crate::node::*;
+use crate::workers::{Worker, WorkerInfo, WorkersBuilder};
+use anyhow::Result;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use wasmi::{Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// Limits of a single call into a module
+#[derive(Clone, Debug)]
+pub struct WasmLimits {
+    pub fuel: u64,
+    pub memory_bytes: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        WasmLimits {
+            fuel: 10_000_000,
+            memory_bytes: 16 << 20,
+        }
+    }
+}
+
+pub struct WasmWorker {
+    info: WorkerInfo,
+    engine: Engine,
+    module: Module,
+    limits: WasmLimits,
+}
+
+struct Instance {
+    store: Store<StoreLimits>,
+    instance: wasmi::Instance,
+    memory: Memory,
+}
+
+impl Instance {
+    fn read(&self, packed: i64) -> Result<Vec<u8>> {
+        let ptr = (packed as u64 >> 32) as usize;
+        let len = (packed as u64 & 0xffff_ffff) as usize;
+        self.memory
+            .data(&self.store)
+            .get(ptr..ptr + len)
+            .map(|bytes| bytes.to_vec())
+            .ok_or_else(|| anyhow!("Result {}..{} is out of the module memory", ptr, ptr + len))
+    }
+}
+
+impl WasmWorker {
+    pub fn load<P: AsRef<Path>>(path: P, limits: WasmLimits) -> Result<WasmWorker> {
+        let path = path.as_ref();
+        WasmWorker::from_bytes(&fs::read(path)?, limits)
+            .map_err(|e| anyhow!("Wasm module `{}`: {}", path.display(), e))
+    }
+
+    pub fn from_bytes(bytes: &[u8], limits: WasmLimits) -> Result<WasmWorker> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes)?;
+        let mut worker = WasmWorker {
+            info: WorkerInfo {
+                name: String::new(),
+                inputs: vec![],
+                outputs: vec![],
+            },
+            engine,
+            module,
+            limits,
+        };
+        let mut instance = worker.instantiate()?;
+        let packed = instance
+            .instance
+            .get_typed_func::<(), i64>(&instance.store, "d3ne_info")?
+            .call(&mut instance.store, ())?;
+        worker.info = serde_json::from_slice(&instance.read(packed)?)?;
+        Ok(worker)
+    }
+
+    /// Loads every `.wasm` file in `dir` in file name order and adds it to `workers`
+    pub fn load_dir<P: AsRef<Path>>(
+        dir: P,
+        limits: WasmLimits,
+        workers: &mut WorkersBuilder,
+    ) -> Result<Vec<WorkerInfo>> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.retain(|path| path.extension().is_some_and(|e| e == "wasm"));
+        paths.sort();
+        let mut infos = vec![];
+        for path in paths {
+            let worker = WasmWorker::load(&path, limits.clone())?;
+            infos.push(worker.info.clone());
+            workers.add(worker);
+        }
+        Ok(infos)
+    }
+
+    fn instantiate(&self) -> Result<Instance> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.memory_bytes)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .add_fuel(self.limits.fuel)
+            .map_err(|e| anyhow!("{}", e))?;
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &self.module)?
+            .start(&mut store)?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| anyhow!("Module does not export `memory`"))?;
+        Ok(Instance {
+            store,
+            instance,
+            memory,
+        })
+    }
+}
+
+impl Worker for WasmWorker {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn info(&self) -> WorkerInfo {
+        self.info.clone()
+    }
+
+    fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData> {
+        let inputs = node.get_input_values(&input_data)?;
+        let request = serde_json::to_vec(&json!({ "node": node, "inputs": inputs }))?;
+
+        let mut instance = self.instantiate()?;
+        let ptr = instance
+            .instance
+            .get_typed_func::<i32, i32>(&instance.store, "d3ne_alloc")?
+            .call(&mut instance.store, request.len() as i32)?;
+        instance
+            .memory
+            .write(&mut instance.store, ptr as u32 as usize, &request)
+            .map_err(|e| anyhow!("Writing the request: {}", e))?;
+        let packed = instance
+            .instance
+            .get_typed_func::<(i32, i32), i64>(&instance.store, "d3ne_work")?
+            .call(&mut instance.store, (ptr, request.len() as i32))?;
+
+        let response: Value = serde_json::from_slice(&instance.read(packed)?)?;
+        if let Some(error) = response.get("error") {
+            bail!(
+                "{}",
+                error
+                    .as_str()
+                    .map_or_else(|| error.to_string(), String::from)
+            );
+        }
+        let outputs = match response.get("outputs") {
+            Some(Value::Object(outputs)) => outputs,
+            _ => bail!("Wasm module returned no `outputs` map"),
+        };
+        let mut builder = OutputDataBuilder::new();
+        for (name, value) in outputs {
+            builder.add_data(name, Box::new(value.clone()));
+        }
+        Ok(builder.build())
+    }
+}
@@ -0,0 +1,224 @@
+//! Keeps the graphs of a directory loaded while they are edited
+//!
+//! Changes are picked up by `GraphWatcher::poll` or `GraphWatcher::wait` on the thread owning
+//! the `Engine`, other threads read the current graphs through `GraphWatcher::graphs`. A file is
+//! reloaded once it has not changed for the debounce window, so that editors truncating and
+//! then writing a file do not report the empty file as a failed reload.
+use crate::engine::Engine;
+use crate::format::GraphFormat;
+use crate::graph::Graph;
+use anyhow::Result;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// How long a file has to stay unchanged before it is reloaded, see `GraphWatcher::set_debounce`
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Current graph of every file that loaded and validated, shared with the threads running them.
+/// Run them with the path as `RunContext::graph_id` to keep the node state of the files apart
+pub type WatchedGraphs = Arc<RwLock<HashMap<PathBuf, Arc<Graph>>>>;
+
+/// Outcome of reloading one file
+#[derive(Debug)]
+pub enum Reload {
+    Loaded {
+        path: PathBuf,
+        graph: Arc<Graph>,
+    },
+    /// The previous graph of the file, if any, stays in place
+    Failed {
+        path: PathBuf,
+        error: anyhow::Error,
+    },
+    Removed {
+        path: PathBuf,
+    },
+}
+
+impl Reload {
+    pub fn path(&self) -> &Path {
+        match self {
+            Reload::Loaded { path, .. }
+            | Reload::Failed { path, .. }
+            | Reload::Removed { path } => path,
+        }
+    }
+}
+
+pub type ReloadCallback = Box<dyn Fn(&Reload)>;
+
+pub struct GraphWatcher {
+    dir: PathBuf,
+    graphs: WatchedGraphs,
+    events: Receiver<notify::Result<notify::Event>>,
+    /// Changed files with the time of their last change
+    pending: RefCell<HashMap<PathBuf, Instant>>,
+    debounce: Duration,
+    on_reload: Option<ReloadCallback>,
+    _watcher: RecommendedWatcher,
+}
+
+impl GraphWatcher {
+    /// Starts watching `dir`, call `reload_all` to load the files already in it
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<GraphWatcher> {
+        let dir = fs::canonicalize(dir.as_ref())
+            .map_err(|e| anyhow!("Cannot watch `{}`: {}", dir.as_ref().display(), e))?;
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        Ok(GraphWatcher {
+            dir,
+            graphs: WatchedGraphs::default(),
+            events,
+            pending: RefCell::default(),
+            debounce: DEFAULT_DEBOUNCE,
+            on_reload: None,
+            _watcher: watcher,
+        })
+    }
+
+    /// Called with the result of every reload
+    pub fn set_on_reload<F>(&mut self, on_reload: F)
+    where
+        F: Fn(&Reload) + 'static,
+    {
+        self.on_reload = Some(Box::new(on_reload));
+    }
+
+    pub fn set_debounce(&mut self, debounce: Duration) {
+        self.debounce = debounce;
+    }
+
+    pub fn graphs(&self) -> WatchedGraphs {
+        self.graphs.clone()
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<Arc<Graph>> {
+        let path = self.dir.join(path);
+        self.graphs.read().ok()?.get(&path).cloned()
+    }
+
+    /// Loads every graph file of the directory, returns how many files were reloaded
+    pub fn reload_all(&self, engine: &Engine) -> Result<usize> {
+        let mut paths = fs::read_dir(&self.dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<BTreeSet<_>>>()?;
+        paths.extend(
+            self.graphs
+                .read()
+                .map_err(|e| anyhow!("{}", e))?
+                .keys()
+                .cloned(),
+        );
+        Ok(self.reload(engine, paths))
+    }
+
+    /// Reloads the files that changed and then stayed unchanged for the debounce window,
+    /// without blocking
+    pub fn poll(&self, engine: &Engine) -> Result<usize> {
+        for event in self.events.try_iter() {
+            self.changed(event?);
+        }
+        Ok(self.reload(engine, self.settled()))
+    }
+
+    /// Waits up to `timeout` for changed files to settle, then reloads like `poll`
+    pub fn wait(&self, engine: &Engine, timeout: Duration) -> Result<usize> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let settled = self.settled();
+            if !settled.is_empty() {
+                return Ok(self.reload(engine, settled));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(0);
+            }
+            let next = self
+                .pending
+                .borrow()
+                .values()
+                .map(|changed| *changed + self.debounce)
+                .min()
+                .map_or(deadline, |settles| settles.min(deadline));
+            match self
+                .events
+                .recv_timeout(next.saturating_duration_since(now))
+            {
+                Ok(event) => self.changed(event?),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => bail!("File watcher stopped"),
+            }
+            for event in self.events.try_iter() {
+                self.changed(event?);
+            }
+        }
+    }
+
+    fn changed(&self, event: notify::Event) {
+        let now = Instant::now();
+        let mut pending = self.pending.borrow_mut();
+        for path in event.paths {
+            pending.insert(path, now);
+        }
+    }
+
+    /// Takes the pending files that did not change for the debounce window
+    fn settled(&self) -> BTreeSet<PathBuf> {
+        let now = Instant::now();
+        let mut settled = BTreeSet::new();
+        self.pending.borrow_mut().retain(|path, changed| {
+            if now.duration_since(*changed) < self.debounce {
+                return true;
+            }
+            settled.insert(path.clone());
+            false
+        });
+        settled
+    }
+
+    fn reload(&self, engine: &Engine, paths: BTreeSet<PathBuf>) -> usize {
+        let mut count = 0;
+        for path in paths {
+            if path.parent() != Some(self.dir.as_path()) || GraphFormat::from_path(&path).is_none()
+            {
+                continue;
+            }
+            let reload = if path.is_file() {
+                match load(engine, &path) {
+                    Ok(graph) => {
+                        let graph = Arc::new(graph);
+                        self.write().insert(path.clone(), graph.clone());
+                        Reload::Loaded { path, graph }
+                    }
+                    Err(error) => Reload::Failed { path, error },
+                }
+            } else if self.write().remove(&path).is_some() {
+                Reload::Removed { path }
+            } else {
+                continue;
+            };
+            count += 1;
+            if let Some(on_reload) = &self.on_reload {
+                on_reload(&reload);
+            }
+        }
+        count
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<PathBuf, Arc<Graph>>> {
+        self.graphs.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn load(engine: &Engine, path: &Path) -> Result<Graph> {
+    let graph = engine.load_graph(path)?;
+    engine.validate(&graph)?;
+    Ok(graph)
+}
@@ -1,6 +1,9 @@
+use crate::context::{Context, Extensions, RunContext};
 use crate::node::*;
+use crate::state::MemoryStateStore;
 use anyhow::Result;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,20 +14,150 @@ pub enum WorkerError {
     NodeRunError(i64, anyhow::Error),
 }
 
+/// Name and sockets of a worker that is not written in Rust
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WorkerInfo {
+    pub name: String,
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    #[serde(default)]
+    pub outputs: Vec<String>,
+}
+
+impl WorkerInfo {
+    pub fn new(name: &str, inputs: &[&str], outputs: &[&str]) -> WorkerInfo {
+        WorkerInfo {
+            name: name.to_string(),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+/// `work` is required. The engine calls `work_with_context`, which runs `work` unless a worker
+/// overrides it to read params, extensions or state from the run context
 pub trait Worker {
     fn name(&self) -> &str;
+
+    /// Sockets the worker reads and writes, used to describe it to editors
+    fn info(&self) -> WorkerInfo {
+        WorkerInfo::new(self.name(), &[], &[])
+    }
+
+    /// Called once, from `WorkersBuilder::try_build` or before the first run when the workers
+    /// come from `WorkersBuilder::build`. `shutdown` only runs after `init` succeeded
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn before_run(&self, _context: &RunContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after every run, also when the run failed
+    fn after_run(&self, _context: &RunContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the workers are dropped together with the engine
+    fn shutdown(&self) {}
+
     fn work(&self, node: &Node, input_data: InputData) -> Result<OutputData>;
+
+    fn work_with_context(
+        &self,
+        node: &Node,
+        input_data: InputData,
+        _context: &Context,
+    ) -> Result<OutputData> {
+        self.work(node, input_data)
+    }
 }
 
-pub struct Workers(HashMap<String, Box<dyn Worker>>);
+pub struct Workers(HashMap<String, Box<dyn Worker>>, RefCell<HashSet<String>>);
 
 impl Workers {
+    /// Runs `init` on the workers that were not initialised yet
+    pub fn init(&self) -> Result<()> {
+        for (name, worker) in &self.0 {
+            if self.1.borrow().contains(name) {
+                continue;
+            }
+            worker
+                .init()
+                .map_err(|e| anyhow!("Worker `{}` init failed: {}", name, e))?;
+            self.1.borrow_mut().insert(name.clone());
+        }
+        Ok(())
+    }
+
+    /// Initialises the workers if needed, then runs every `before_run` hook. When one fails,
+    /// the workers whose `before_run` already succeeded get their `after_run` before the error
+    /// is returned
+    pub fn before_run(&self, context: &RunContext) -> Result<()> {
+        self.init()?;
+        let mut started: Vec<&dyn Worker> = vec![];
+        for worker in self.0.values() {
+            if let Err(e) = worker.before_run(context) {
+                for worker in started {
+                    let _ = worker.after_run(context);
+                }
+                return Err(e);
+            }
+            started.push(worker.as_ref());
+        }
+        Ok(())
+    }
+
+    /// Runs every `after_run` hook and returns the first error
+    pub fn after_run(&self, context: &RunContext) -> Result<()> {
+        let results = self
+            .0
+            .values()
+            .map(|worker| worker.after_run(context))
+            .collect::<Vec<_>>();
+        results.into_iter().collect()
+    }
+
+    /// Info of every worker in name order
+    pub fn infos(&self) -> Vec<WorkerInfo> {
+        let mut infos = self.0.values().map(|w| w.info()).collect::<Vec<_>>();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Worker names in alphabetical order
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.0.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
     pub fn call(&self, name: &str, node: &Node, input: InputData) -> Result<OutputData> {
+        self.init()?;
+        let run = RunContext::new();
+        let extensions = Extensions::default();
+        let state = MemoryStateStore::new();
+        let context = Context::new(&run, &extensions, &state, None, node.id, name);
+        self.call_with_context(name, node, input, &context)
+    }
+
+    pub fn call_with_context(
+        &self,
+        name: &str,
+        node: &Node,
+        input: InputData,
+        context: &Context,
+    ) -> Result<OutputData> {
         self.0
             .get(name)
             .map(|worker| {
                 worker
-                    .work(node, input)
+                    .work_with_context(node, input, context)
                     .map_err(|e| anyhow!(WorkerError::NodeRunError(node.id, e)))
             })
             .ok_or(WorkerError::WorkerNotFound(name.into()))?
@@ -50,8 +183,34 @@ impl WorkersBuilder {
         self
     }
 
+    pub(crate) fn into_workers(self) -> Vec<Box<dyn Worker>> {
+        self.data.into_iter().map(|(_, worker)| worker).collect()
+    }
+
+    /// Workers are initialised before the first run, an `init` error fails that run
     pub fn build(self) -> Workers {
-        Workers(self.data.into_iter().collect::<HashMap<_, _>>())
+        Workers(
+            self.data.into_iter().collect::<HashMap<_, _>>(),
+            RefCell::default(),
+        )
+    }
+
+    /// Initialises every worker right away, workers that were initialised before one failed
+    /// are shut down again
+    pub fn try_build(self) -> Result<Workers> {
+        let workers = self.build();
+        workers.init()?;
+        Ok(workers)
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        let initialised = self.1.borrow();
+        self.0
+            .iter()
+            .filter(|(name, _)| initialised.contains(*name))
+            .for_each(|(_, worker)| worker.shutdown());
     }
 }
 
@@ -60,4 +219,3 @@ impl Default for WorkersBuilder {
         Self::new()
     }
 }
-
@@ -0,0 +1,160 @@
+//! Runs the `d3ne` binary on graph files
+#![cfg(feature = "cli")]
+use assert_cmd::assert::Assert;
+use assert_cmd::Command;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+const GRAPH: &str = r#"{"id":"demo@0.1.0","nodes":{
+  "1":{"id":1,"data":{"num":2},"inputs":{},"outputs":{"num":{"connections":[{"node":3,"input":"num","data":{}}]}},"position":[0,0],"name":"Number"},
+  "2":{"id":2,"data":{"num":3},"inputs":{},"outputs":{"num":{"connections":[{"node":3,"input":"num2","data":{}}]}},"position":[0,0],"name":"Number"},
+  "3":{"id":3,"data":{},"inputs":{"num":{"connections":[{"node":1,"output":"num","data":{}}]},"num2":{"connections":[{"node":2,"output":"num","data":{}}]}},"outputs":{"num":{"connections":[]}},"position":[0,0],"name":"Add"}
+}}"#;
+
+fn graph_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("d3ne-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn d3ne(args: &[&str], graph: &Path) -> Assert {
+    Command::cargo_bin("d3ne")
+        .unwrap()
+        .args(args)
+        .arg(graph)
+        .assert()
+}
+
+fn stdout(assert: &Assert) -> String {
+    String::from_utf8(assert.get_output().stdout.clone()).unwrap()
+}
+
+fn stderr(assert: &Assert) -> String {
+    String::from_utf8(assert.get_output().stderr.clone()).unwrap()
+}
+
+#[test]
+fn validate_checks_workers() {
+    let graph = graph_file("validate.json", GRAPH);
+    let valid = d3ne(&["validate"], &graph).success();
+    assert!(stdout(&valid).ends_with("validate.json: 3 nodes ok\n"));
+
+    let graph = graph_file("unknown.json", &GRAPH.replace("\"Add\"", "\"Nope\""));
+    let invalid = d3ne(&["validate"], &graph).failure();
+    assert!(
+        stderr(&invalid).starts_with("error: "),
+        "{}",
+        stderr(&invalid)
+    );
+}
+
+#[test]
+fn run_prints_outputs_and_fails_on_node_errors() {
+    let graph = graph_file("run.json", GRAPH);
+    let run = d3ne(&["run"], &graph).success();
+    let outputs: Value = serde_json::from_str(&stdout(&run)).unwrap();
+    assert_eq!(
+        outputs,
+        json!({ "1": { "num": 2 }, "2": { "num": 3 }, "3": { "num": 5 } })
+    );
+
+    d3ne(&["run", "--start", "9"], &graph).failure();
+
+    let graph = graph_file(
+        "broken.json",
+        &GRAPH.replace("{\"num\":3}", "{\"num\":\"x\"}"),
+    );
+    let broken = d3ne(&["run"], &graph).failure();
+    assert!(
+        stderr(&broken).starts_with("node 2: "),
+        "{}",
+        stderr(&broken)
+    );
+}
+
+#[test]
+fn fmt_prints_and_rewrites_canonical_json() {
+    let graph = graph_file("fmt.json", GRAPH);
+    let printed = stdout(&d3ne(&["fmt"], &graph).success());
+    assert!(printed.starts_with("{\n  \"id\": \"demo@0.1.0\",\n  \"nodes\": {\n    \"1\": {"));
+    assert_eq!(
+        serde_json::from_str::<Value>(&printed).unwrap(),
+        serde_json::from_str::<Value>(GRAPH).unwrap()
+    );
+
+    d3ne(&["fmt", "--write"], &graph).success();
+    assert_eq!(
+        std::fs::read_to_string(&graph).unwrap().trim(),
+        printed.trim()
+    );
+}
+
+#[test]
+fn stats_counts_nodes_per_worker() {
+    let graph = graph_file("stats.json", GRAPH);
+    let stats = stdout(&d3ne(&["stats"], &graph).success());
+    assert_eq!(
+        stats,
+        "graph       demo@0.1.0\nnodes       3\nconnections 2\ngroups      0\ncomments    0\n  Add        1\n  Number     2\n"
+    );
+}
+
+#[test]
+fn trace_prints_events_with_timings() {
+    let graph = graph_file("trace.json", GRAPH);
+    let trace = stdout(&d3ne(&["trace"], &graph).success());
+    let lines: Vec<Vec<&str>> = trace
+        .lines()
+        .map(|l| l.split_whitespace().collect())
+        .collect();
+    let events: Vec<_> = lines.iter().map(|l| (l[0], l[1], l[2])).collect();
+    assert_eq!(
+        events,
+        [
+            ("start", "1", "Number"),
+            ("done", "1", "Number"),
+            ("start", "2", "Number"),
+            ("done", "2", "Number"),
+            ("start", "3", "Add"),
+            ("done", "3", "Add"),
+        ]
+    );
+    for done in lines.iter().filter(|l| l[0] == "done") {
+        assert!(done[3].ends_with('s'), "{:?}", done);
+    }
+    assert_eq!(lines[5][4], "{\"num\":5}");
+
+    let graph = graph_file(
+        "trace-broken.json",
+        &GRAPH.replace("{\"num\":3}", "{\"num\":\"x\"}"),
+    );
+    let broken = stdout(&d3ne(&["trace"], &graph).failure());
+    assert!(broken.contains("\nfailed 2 Number "), "{}", broken);
+    assert!(broken.ends_with("skip   3\n"), "{}", broken);
+}
+
+#[test]
+fn export_prints_dot_and_mermaid() {
+    let graph = graph_file("export.json", GRAPH);
+    let dot = stdout(&d3ne(&["export"], &graph).success());
+    assert_eq!(
+        dot,
+        "digraph {\n  rankdir=LR;\n  node [shape=box];\n  n1 [label=\"1: Number\"];\n  n2 [label=\"2: Number\"];\n  n3 [label=\"3: Add\"];\n  n1 -> n3 [label=\"num → num\"];\n  n2 -> n3 [label=\"num → num2\"];\n}\n"
+    );
+
+    let mermaid = stdout(&d3ne(&["export", "--format", "mermaid"], &graph).success());
+    assert_eq!(
+        mermaid,
+        "flowchart LR\n  n1[\"1: Number\"]\n  n2[\"2: Number\"]\n  n3[\"3: Add\"]\n  n1 -- \"num → num\" --> n3\n  n2 -- \"num → num2\" --> n3\n"
+    );
+
+    let overlay = stdout(&d3ne(&["export", "--format", "mermaid", "--run"], &graph).success());
+    assert!(overlay.starts_with(&mermaid), "{}", overlay);
+    assert!(
+        overlay.ends_with("  class n1,n2,n3 executed\n"),
+        "{}",
+        overlay
+    );
+}